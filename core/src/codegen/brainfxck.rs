@@ -0,0 +1,934 @@
+//! Regenerates `BrainFxck` from a program sequenced without states. Values
+//! are followed symbolically, so that pointers become head movement and
+//! arithmetic on cells becomes the shortest run of `+` or `-`. Loops must
+//! keep the shape the parser gives them, a `Select` that skips a `Repeat`,
+//! and values can only be written to the cells they came from, as copying
+//! would need scratch cells.
+
+use std::{
+	collections::{BTreeMap, BTreeSet},
+	fmt::{Display, Formatter},
+};
+
+use crate::mir::data::{Instruction, Program};
+
+use super::usage::{is_read_in, is_written_first};
+
+#[derive(Debug)]
+pub enum RegenerateError {
+	UnknownValue { register: u32 },
+	UnknownPointer { register: u32 },
+	UnknownCondition { register: u32 },
+	UnsupportedSelect,
+	UnsupportedRepeat,
+	UnsupportedCopy { register: u32 },
+	UnsupportedOutput { register: u32 },
+	UnreadInput,
+	UnbalancedLoop,
+	LoopCarried { register: u32 },
+}
+
+impl Display for RegenerateError {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::UnknownValue { register } => write!(f, "value of `r{register}` is not known"),
+			Self::UnknownPointer { register } => {
+				write!(f, "`r{register}` is not a known pointer")
+			}
+			Self::UnknownCondition { register } => {
+				write!(f, "condition `r{register}` is not a known cell")
+			}
+			Self::UnsupportedSelect => write!(f, "`select` is not shaped like a loop"),
+			Self::UnsupportedRepeat => write!(f, "`repeat` is not inside a `select`"),
+			Self::UnsupportedCopy { register } => {
+				write!(f, "`r{register}` would have to be copied to another cell")
+			}
+			Self::UnsupportedOutput { register } => {
+				write!(f, "`r{register}` is written out but is in no cell")
+			}
+			Self::UnreadInput => write!(f, "input is read but never stored"),
+			Self::UnbalancedLoop => write!(f, "loop moves the head but uses fixed cells"),
+			Self::LoopCarried { register } => {
+				write!(f, "`r{register}` does not match between loop iterations")
+			}
+		}
+	}
+}
+
+impl std::error::Error for RegenerateError {}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Base {
+	Zero,
+	// The content of a cell when its frame was entered.
+	Entry { frame: usize, offset: i64 },
+	// The content of a cell after a loop changed it.
+	Unknown(usize),
+	Input(usize),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct Term {
+	base: Base,
+	delta: u8,
+}
+
+impl Term {
+	const ZERO: Self = Self::from_base(Base::Zero);
+
+	const fn from_base(base: Base) -> Self {
+		Self { base, delta: 0 }
+	}
+
+	const fn with_delta(self, delta: u8) -> Self {
+		Self {
+			base: self.base,
+			delta: self.delta.wrapping_add(delta),
+		}
+	}
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Value {
+	Number(i64),
+	// An offset from the origin of the current frame. It stays fixed in the
+	// frames deeper than `depth`, as it was not changed by them.
+	Pointer { offset: i64, depth: usize },
+	// A byte, which was last known to be in `cell`.
+	Byte { term: Term, cell: Option<i64> },
+}
+
+impl Value {
+	#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+	const fn to_term(self) -> Option<Term> {
+		match self {
+			Self::Number(number) => Some(Term {
+				base: Base::Zero,
+				delta: number as u8,
+			}),
+			Self::Pointer { .. } => None,
+			Self::Byte { term, .. } => Some(term),
+		}
+	}
+
+	const fn cell(self) -> Option<i64> {
+		match self {
+			Self::Byte { cell, .. } => cell,
+			_ => None,
+		}
+	}
+}
+
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+fn add(lhs: Option<Value>, rhs: Option<Value>) -> Option<Value> {
+	match (lhs?, rhs?) {
+		(Value::Number(lhs), Value::Number(rhs)) => Some(Value::Number(lhs.wrapping_add(rhs))),
+		(Value::Pointer { offset, depth }, Value::Number(number))
+		| (Value::Number(number), Value::Pointer { offset, depth }) => Some(Value::Pointer {
+			offset: offset.wrapping_add(number),
+			depth,
+		}),
+		(Value::Byte { term, cell }, Value::Number(number))
+		| (Value::Number(number), Value::Byte { term, cell }) => Some(Value::Byte {
+			term: term.with_delta(number as u8),
+			cell,
+		}),
+		_ => None,
+	}
+}
+
+fn sub(lhs: Option<Value>, rhs: Option<Value>) -> Option<Value> {
+	match (lhs?, rhs?) {
+		(lhs, Value::Number(number)) => add(Some(lhs), Some(Value::Number(number.wrapping_neg()))),
+		(Value::Pointer { offset: lhs, .. }, Value::Pointer { offset: rhs, .. }) => {
+			Some(Value::Number(lhs.wrapping_sub(rhs)))
+		}
+		_ => None,
+	}
+}
+
+// Pointers from before the loop are compared as the numbers they came from
+// when they were never moved in a frame.
+fn merge(taken: Option<Value>, skipped: Option<Value>, origin: Option<i64>) -> Option<Value> {
+	match (taken?, skipped?) {
+		(
+			Value::Pointer { offset, depth },
+			Value::Pointer {
+				offset: other,
+				depth: level,
+			},
+		) => (offset == other).then_some(Value::Pointer {
+			offset,
+			depth: depth.min(level),
+		}),
+		(Value::Pointer { offset, depth }, Value::Number(number)) => {
+			(origin.map(|origin| origin + offset) == Some(number))
+				.then_some(Value::Pointer { offset, depth })
+		}
+		(taken, skipped) => (taken == skipped).then_some(taken),
+	}
+}
+
+// The shortest way from one byte to another, positive for `+` and negative
+// for `-`.
+fn shortest(from: u8, to: u8) -> i16 {
+	let delta = to.wrapping_sub(from);
+
+	if delta <= 128 {
+		i16::from(delta)
+	} else {
+		i16::from(delta) - 256
+	}
+}
+
+const fn is_pure(insn: &Instruction) -> bool {
+	!matches!(
+		insn,
+		Instruction::Store { .. }
+			| Instruction::Ask { .. }
+			| Instruction::Tell { .. }
+			| Instruction::Select { .. }
+			| Instruction::Repeat { .. }
+	)
+}
+
+// Splits a body into what comes before its only loop, the loop, and what
+// comes after, as long as nothing else in it has effects.
+fn split_loop(list: &[Instruction]) -> Option<(&[Instruction], usize, u32, &[Instruction])> {
+	let position = list
+		.iter()
+		.position(|insn| matches!(insn, Instruction::Repeat { .. }))?;
+
+	let Instruction::Repeat { code, condition } = list[position] else {
+		unreachable!()
+	};
+
+	let before = &list[..position];
+	let after = &list[position + 1..];
+
+	before
+		.iter()
+		.chain(after)
+		.all(is_pure)
+		.then_some((before, code, condition, after))
+}
+
+fn collect_writes(bodies: &[Box<[Instruction]>], code: usize, writes: &mut BTreeSet<u32>) {
+	for insn in &bodies[code] {
+		writes.extend(insn.writes());
+
+		match insn {
+			Instruction::Select { code, .. } => {
+				for &code in code {
+					collect_writes(bodies, code, writes);
+				}
+			}
+			Instruction::Repeat { code, .. } => collect_writes(bodies, *code, writes),
+			_ => {}
+		}
+	}
+}
+
+// The program or a loop body, with offsets relative to the head on entry.
+// The logical cells are what the program expects, while the physical ones
+// differ only after output, which adjusts cells that are restored lazily.
+#[allow(clippy::struct_excessive_bools)]
+struct Frame {
+	id: usize,
+	origin: Option<i64>,
+	head: i64,
+
+	is_zeroed: bool,
+	is_fixed_used: bool,
+	is_shifted: bool,
+	is_unbounded: bool,
+
+	logical: BTreeMap<i64, Term>,
+	physical: BTreeMap<i64, Term>,
+	touched: BTreeSet<i64>,
+}
+
+impl Frame {
+	const fn new(id: usize, origin: Option<i64>) -> Self {
+		Self {
+			id,
+			origin,
+			head: 0,
+
+			is_zeroed: false,
+			is_fixed_used: false,
+			is_shifted: false,
+			is_unbounded: false,
+
+			logical: BTreeMap::new(),
+			physical: BTreeMap::new(),
+			touched: BTreeSet::new(),
+		}
+	}
+
+	const fn initial(&self, offset: i64) -> Term {
+		if self.is_zeroed {
+			Term::ZERO
+		} else {
+			Term::from_base(Base::Entry {
+				frame: self.id,
+				offset,
+			})
+		}
+	}
+
+	fn logical(&self, offset: i64) -> Term {
+		self.logical
+			.get(&offset)
+			.copied()
+			.unwrap_or_else(|| self.initial(offset))
+	}
+
+	fn physical(&self, offset: i64) -> Term {
+		self.physical
+			.get(&offset)
+			.copied()
+			.unwrap_or_else(|| self.logical(offset))
+	}
+
+	fn is_free(&self, offset: i64) -> bool {
+		!self.logical.contains_key(&offset) && !self.physical.contains_key(&offset)
+	}
+
+	// Finds a cell that is expected to hold the term.
+	fn find(&self, term: Term, hint: Option<i64>) -> Option<i64> {
+		if let Some(offset) = hint.filter(|&offset| self.logical(offset) == term) {
+			return Some(offset);
+		}
+
+		if let Some((&offset, _)) = self.logical.iter().find(|entry| *entry.1 == term) {
+			return Some(offset);
+		}
+
+		match term.base {
+			Base::Entry { frame, offset }
+				if frame == self.id && term.delta == 0 && !self.logical.contains_key(&offset) =>
+			{
+				Some(offset)
+			}
+			_ => None,
+		}
+	}
+
+	// Finds the cell that can be turned into the term the quickest. Only a
+	// few cells are ever known, so a free one is always near.
+	#[allow(clippy::maybe_infinite_iter)]
+	fn find_nearest(&self, term: Term) -> Option<i64> {
+		let mut list: BTreeSet<i64> = self
+			.logical
+			.keys()
+			.chain(self.physical.keys())
+			.copied()
+			.collect();
+
+		match term.base {
+			Base::Entry { frame, offset } if frame == self.id => {
+				list.insert(offset);
+			}
+			Base::Zero if self.is_zeroed => {
+				let free = (0..)
+					.flat_map(|distance| [self.head + distance, self.head - distance])
+					.find(|&offset| self.is_free(offset))
+					.unwrap();
+
+				list.insert(free);
+			}
+			_ => {}
+		}
+
+		list.into_iter()
+			.filter(|&offset| self.physical(offset).base == term.base)
+			.min_by_key(|&offset| {
+				let steps = shortest(self.physical(offset).delta, term.delta);
+
+				(offset - self.head).abs() + i64::from(steps.abs())
+			})
+	}
+}
+
+/// Regenerates `BrainFxck` source from a program, such that it can be fed
+/// back in to check the optimizer or to ship smaller code. Input that ends
+/// is read as whatever the running implementation gives on end of file.
+#[derive(Default)]
+pub struct Regenerator {
+	code: String,
+	frames: Vec<Frame>,
+	registers: Vec<Option<Value>>,
+	pending: Option<usize>,
+	next_id: usize,
+}
+
+impl Regenerator {
+	#[must_use]
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	fn fresh(&mut self) -> usize {
+		self.next_id += 1;
+		self.next_id
+	}
+
+	fn frame(&self) -> &Frame {
+		self.frames.last().unwrap()
+	}
+
+	fn frame_mut(&mut self) -> &mut Frame {
+		self.frames.last_mut().unwrap()
+	}
+
+	fn get(&self, register: u32) -> Option<Value> {
+		self.registers[register as usize]
+	}
+
+	fn set(&mut self, register: u32, value: Option<Value>) {
+		self.registers[register as usize] = value;
+	}
+
+	fn push_steps(&mut self, count: u64, forward: char, backward: char, is_forward: bool) {
+		let step = if is_forward { forward } else { backward };
+
+		self.code
+			.extend(std::iter::repeat_n(step, count.try_into().unwrap()));
+	}
+
+	fn seek(&mut self, offset: i64) {
+		let distance = offset - self.frame().head;
+
+		self.push_steps(distance.unsigned_abs(), '>', '<', distance >= 0);
+		self.frame_mut().head = offset;
+	}
+
+	fn adjust(&mut self, from: u8, to: u8) {
+		let steps = shortest(from, to);
+
+		self.push_steps(steps.unsigned_abs().into(), '+', '-', steps >= 0);
+	}
+
+	fn check_input(&self) -> Result<(), RegenerateError> {
+		if self.pending.is_some() {
+			Err(RegenerateError::UnreadInput)
+		} else {
+			Ok(())
+		}
+	}
+
+	fn mark_fixed(&mut self, depth: usize) {
+		for frame in self.frames.iter_mut().skip(depth + 1) {
+			frame.is_fixed_used = true;
+		}
+	}
+
+	fn resolve(&mut self, pointer: u32, state: Option<u32>) -> Result<i64, RegenerateError> {
+		let value = match state {
+			Some(state) => add(self.get(pointer), self.get(state)),
+			None => self.get(pointer),
+		};
+
+		match value {
+			Some(Value::Pointer { offset, depth }) => {
+				self.mark_fixed(depth);
+
+				Ok(offset)
+			}
+			Some(Value::Number(number)) => {
+				let origin = self
+					.frame()
+					.origin
+					.ok_or(RegenerateError::UnknownPointer { register: pointer })?;
+
+				self.mark_fixed(0);
+
+				Ok(number - origin)
+			}
+			_ => Err(RegenerateError::UnknownPointer { register: pointer }),
+		}
+	}
+
+	// Physically turns the cell into the term, which only works if it is
+	// the same value, a constant, or input that was not read yet.
+	fn transition(
+		&mut self,
+		offset: i64,
+		term: Term,
+		register: u32,
+	) -> Result<(), RegenerateError> {
+		let current = self.frame().physical(offset);
+
+		if current.base == term.base {
+			self.seek(offset);
+			self.adjust(current.delta, term.delta);
+		} else if term.base == Base::Zero {
+			self.seek(offset);
+			self.code.push_str("[-]");
+			self.adjust(0, term.delta);
+		} else if matches!(term.base, Base::Input(id) if self.pending == Some(id)) {
+			self.seek(offset);
+			self.code.push(',');
+			self.adjust(0, term.delta);
+			self.pending = None;
+		} else {
+			return Err(RegenerateError::UnsupportedCopy { register });
+		}
+
+		Ok(())
+	}
+
+	// Restores every cell changed by output, nearest end first.
+	fn flush(&mut self) {
+		let physical = std::mem::take(&mut self.frame_mut().physical);
+		let mut list: Vec<_> = physical.into_iter().collect();
+
+		if let (Some(first), Some(last)) = (list.first(), list.last()) {
+			let head = self.frame().head;
+
+			if (last.0 - head).abs() < (first.0 - head).abs() {
+				list.reverse();
+			}
+		}
+
+		for (offset, term) in list {
+			let target = self.frame().logical(offset);
+
+			self.seek(offset);
+			self.adjust(term.delta, target.delta);
+		}
+	}
+
+	fn run_store(&mut self, offset: i64, value: u32) -> Result<(), RegenerateError> {
+		let term = self
+			.get(value)
+			.and_then(Value::to_term)
+			.ok_or(RegenerateError::UnknownValue { register: value })?;
+
+		self.transition(offset, term, value)?;
+
+		let frame = self.frame_mut();
+
+		frame.physical.remove(&offset);
+		frame.logical.insert(offset, term);
+		frame.touched.insert(offset);
+
+		Ok(())
+	}
+
+	fn run_tell(&mut self, value: u32) -> Result<(), RegenerateError> {
+		self.check_input()?;
+
+		let term = self
+			.get(value)
+			.and_then(Value::to_term)
+			.ok_or(RegenerateError::UnknownValue { register: value })?;
+
+		let offset = self
+			.frame()
+			.find_nearest(term)
+			.ok_or(RegenerateError::UnsupportedOutput { register: value })?;
+
+		self.transition(offset, term, value)?;
+		self.code.push('.');
+
+		let frame = self.frame_mut();
+
+		if frame.logical(offset) == term {
+			frame.physical.remove(&offset);
+		} else {
+			frame.physical.insert(offset, term);
+		}
+
+		Ok(())
+	}
+
+	fn find_condition(&self, condition: u32) -> Result<(Term, i64), RegenerateError> {
+		let error = || RegenerateError::UnknownCondition {
+			register: condition,
+		};
+
+		let value = self.get(condition).ok_or_else(error)?;
+		let term = value.to_term().ok_or_else(error)?;
+		let offset = self.frame().find(term, value.cell()).ok_or_else(error)?;
+
+		Ok((term, offset))
+	}
+
+	// Converts the registers for use in a loop body, where those written
+	// before they are read hold nothing.
+	fn enter_loop(
+		&mut self,
+		bodies: &[Box<[Instruction]>],
+		code: usize,
+		written: &BTreeSet<u32>,
+		start: i64,
+	) -> Vec<(u32, Value)> {
+		let depth = self.frames.len();
+		let origin = self.frame().origin.map(|origin| origin + start);
+		let id = self.fresh();
+		let mut carried = Vec::new();
+
+		for register in 0..self.registers.len() {
+			let register = u32::try_from(register).unwrap();
+			let is_written = written.contains(&register);
+			let value = match self.get(register) {
+				Some(Value::Pointer {
+					offset,
+					depth: level,
+				}) => Some(Value::Pointer {
+					offset: offset - start,
+					depth: if is_written { depth } else { level },
+				}),
+				Some(Value::Number(number)) if is_written => origin.map(|origin| Value::Pointer {
+					offset: number - origin,
+					depth,
+				}),
+				Some(Value::Byte { term, cell }) if is_written => {
+					self.frame().find(term, cell).map(|offset| {
+						let offset = offset - start;
+
+						Value::Byte {
+							term: Term::from_base(Base::Entry { frame: id, offset }),
+							cell: Some(offset),
+						}
+					})
+				}
+				value => value,
+			};
+
+			let value = if is_written {
+				let is_carried =
+					is_read_in(bodies, code, register) && !is_written_first(bodies, code, register);
+
+				value.filter(|_| is_carried)
+			} else {
+				value
+			};
+
+			if let Some(value) = value.filter(|_| is_written) {
+				carried.push((register, value));
+			}
+
+			self.set(register, value);
+		}
+
+		self.frames.push(Frame::new(id, origin));
+
+		carried
+	}
+
+	// Checks that every carried value is where the next iteration expects
+	// it, relative to the cell tested at the end.
+	fn check_carried(&self, carried: &[(u32, Value)], end: i64) -> Result<(), RegenerateError> {
+		for &(register, entry) in carried {
+			let exit = self.get(register);
+			let is_consistent = match entry {
+				Value::Pointer { offset, .. } => {
+					matches!(exit, Some(Value::Pointer { offset: last, .. }) if last == offset + end)
+				}
+				Value::Byte {
+					cell: Some(cell), ..
+				} => exit.and_then(Value::to_term) == Some(self.frame().logical(cell + end)),
+				_ => exit == Some(entry),
+			};
+
+			if !is_consistent {
+				return Err(RegenerateError::LoopCarried { register });
+			}
+		}
+
+		Ok(())
+	}
+
+	// Converts the registers back after a loop, which is balanced if it
+	// always ends where it started.
+	fn leave_loop(&mut self, written: &BTreeSet<u32>, start: i64, end: i64) -> bool {
+		let frame = self.frames.pop().unwrap();
+		let is_balanced = end == 0 && !frame.is_shifted;
+		let level = self.frames.len() - 1;
+
+		for register in 0..self.registers.len() {
+			let register = u32::try_from(register).unwrap();
+			let is_written = written.contains(&register);
+			let value = match self.get(register) {
+				Some(Value::Pointer { offset, depth }) if is_balanced => Some(Value::Pointer {
+					offset: offset + start,
+					depth: depth.min(level),
+				}),
+				Some(Value::Pointer { offset, depth }) if is_written => Some(Value::Pointer {
+					offset: offset - end,
+					depth: depth.min(level),
+				}),
+				value @ Some(Value::Number(_)) => value,
+				value @ Some(Value::Byte { .. }) if is_balanced && !is_written => value,
+				_ => None,
+			};
+
+			self.set(register, value);
+		}
+
+		if is_balanced && !frame.is_unbounded {
+			for offset in frame.touched {
+				let id = self.fresh();
+				let parent = self.frame_mut();
+
+				parent
+					.logical
+					.insert(offset + start, Term::from_base(Base::Unknown(id)));
+
+				parent.touched.insert(offset + start);
+			}
+		} else {
+			let id = self.fresh();
+			let parent = self.frame_mut();
+
+			parent.id = id;
+			parent.is_zeroed = false;
+			parent.is_unbounded = true;
+			parent.logical.clear();
+			parent.touched.clear();
+
+			if !is_balanced {
+				parent.origin = None;
+				parent.is_shifted = true;
+			}
+		}
+
+		let start = if is_balanced { start } else { 0 };
+		let parent = self.frame_mut();
+
+		parent.head = start;
+		parent.logical.insert(start, Term::ZERO);
+		parent.touched.insert(start);
+
+		is_balanced
+	}
+
+	fn run_repeat(
+		&mut self,
+		bodies: &[Box<[Instruction]>],
+		code: usize,
+		condition: u32,
+		start: i64,
+	) -> Result<bool, RegenerateError> {
+		let mut written = BTreeSet::new();
+
+		collect_writes(bodies, code, &mut written);
+
+		let carried = self.enter_loop(bodies, code, &written, start);
+
+		self.code.push('[');
+		self.run_block(bodies, code)?;
+
+		let (_, end) = self.find_condition(condition)?;
+
+		self.check_input()?;
+		self.flush();
+		self.seek(end);
+		self.code.push(']');
+
+		self.check_carried(&carried, end)?;
+
+		if (end != 0 || self.frame().is_shifted) && self.frame().is_fixed_used {
+			return Err(RegenerateError::UnbalancedLoop);
+		}
+
+		Ok(self.leave_loop(&written, start, end))
+	}
+
+	fn run_select(
+		&mut self,
+		bodies: &[Box<[Instruction]>],
+		condition: u32,
+		code: &[usize],
+	) -> Result<(), RegenerateError> {
+		let &[skip, taken] = code else {
+			return Err(RegenerateError::UnsupportedSelect);
+		};
+
+		let (before, repeat, repeat_condition, after) = split_loop(&bodies[taken])
+			.filter(|_| bodies[skip].iter().all(is_pure))
+			.ok_or(RegenerateError::UnsupportedSelect)?;
+
+		let (term, start) = self.find_condition(condition)?;
+
+		// A loop that cannot start is left out entirely.
+		if term == Term::ZERO {
+			return self.run_block(bodies, skip);
+		}
+
+		self.check_input()?;
+		self.flush();
+		self.seek(start);
+
+		let origin = self.frame().origin;
+		let entry = self.registers.clone();
+
+		self.run_block(bodies, skip)?;
+
+		let skipped = std::mem::replace(&mut self.registers, entry);
+
+		self.run_list(bodies, before)?;
+
+		let is_balanced = self.run_repeat(bodies, repeat, repeat_condition, start)?;
+
+		self.run_list(bodies, after)?;
+
+		let shift = if is_balanced { 0 } else { start };
+		let origin = origin.map(|origin| origin + shift);
+
+		for (register, skipped) in skipped.into_iter().enumerate() {
+			let skipped = match skipped {
+				Some(Value::Pointer { offset, depth }) => Some(Value::Pointer {
+					offset: offset - shift,
+					depth,
+				}),
+				Some(Value::Byte { .. }) if !is_balanced => None,
+				value => value,
+			};
+
+			self.registers[register] = merge(self.registers[register], skipped, origin);
+		}
+
+		Ok(())
+	}
+
+	#[allow(clippy::cast_possible_wrap)]
+	fn run_insn(
+		&mut self,
+		bodies: &[Box<[Instruction]>],
+		insn: &Instruction,
+	) -> Result<(), RegenerateError> {
+		match *insn {
+			Instruction::Memory { result } | Instruction::IO { result } => {
+				self.set(result, Some(Value::Number(0)));
+			}
+			Instruction::Integer { result, value } => {
+				self.set(result, Some(Value::Number(value as i64)));
+			}
+			Instruction::Move { from, to } => self.set(to, self.get(from)),
+			Instruction::Add { result, lhs, rhs } => {
+				self.set(result, add(self.get(lhs), self.get(rhs)));
+			}
+			Instruction::Sub { result, lhs, rhs } => {
+				self.set(result, sub(self.get(lhs), self.get(rhs)));
+			}
+			Instruction::Load {
+				result,
+				pointer,
+				state,
+			} => {
+				let offset = self.resolve(pointer, state)?;
+				let term = self.frame().logical(offset);
+
+				self.set(
+					result,
+					Some(Value::Byte {
+						term,
+						cell: Some(offset),
+					}),
+				);
+			}
+			Instruction::Store {
+				pointer,
+				value,
+				state,
+			} => {
+				let offset = self.resolve(pointer, state)?;
+
+				self.run_store(offset, value)?;
+			}
+			Instruction::Ask { result, .. } => {
+				self.check_input()?;
+
+				let id = self.fresh();
+				let term = Term::from_base(Base::Input(id));
+
+				self.pending = Some(id);
+				self.set(result, Some(Value::Byte { term, cell: None }));
+			}
+			Instruction::Tell { value, .. } => self.run_tell(value)?,
+			Instruction::Select {
+				condition,
+				ref code,
+			} => self.run_select(bodies, condition, code)?,
+			Instruction::Repeat { .. } => return Err(RegenerateError::UnsupportedRepeat),
+		}
+
+		Ok(())
+	}
+
+	fn run_list(
+		&mut self,
+		bodies: &[Box<[Instruction]>],
+		list: &[Instruction],
+	) -> Result<(), RegenerateError> {
+		list.iter().try_for_each(|insn| self.run_insn(bodies, insn))
+	}
+
+	fn run_block(
+		&mut self,
+		bodies: &[Box<[Instruction]>],
+		code: usize,
+	) -> Result<(), RegenerateError> {
+		self.run_list(bodies, &bodies[code])
+	}
+
+	/// Regenerates the source of the program.
+	///
+	/// # Errors
+	///
+	/// Returns an error if some part of the program has no direct form in
+	/// `BrainFxck`, such as a value copied to another cell.
+	pub fn run(&mut self, program: &Program) -> Result<&str, RegenerateError> {
+		let mut frame = Frame::new(0, Some(0));
+
+		frame.is_zeroed = true;
+
+		self.code.clear();
+		self.frames.clear();
+		self.frames.push(frame);
+		self.registers.clear();
+		self.registers.resize(program.locals(), None);
+		self.pending = None;
+		self.next_id = 0;
+
+		if !program.bodies().is_empty() {
+			self.run_block(program.bodies(), 0)?;
+		}
+
+		Ok(&self.code)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::mir::text::Reader;
+
+	use super::{RegenerateError, Regenerator};
+
+	fn regenerate(text: &str) -> Result<String, RegenerateError> {
+		let program = Reader::new().read(text).unwrap();
+
+		Regenerator::new().run(&program).map(str::to_string)
+	}
+
+	#[test]
+	fn regenerates_echo() {
+		let text = "locals 2\nr0 = integer 0\nr1 = ask\nstore r0, r1\nr1 = load r0\ntell r1\n";
+
+		assert_eq!(regenerate(text).unwrap(), ",.");
+	}
+
+	#[test]
+	fn rejects_select_of_three() {
+		let text = "locals 1\nr0 = integer 0\nselect r0 {\n} {\n} {\n}\n";
+
+		assert!(matches!(
+			regenerate(text),
+			Err(RegenerateError::UnsupportedSelect)
+		));
+	}
+}
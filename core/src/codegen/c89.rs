@@ -0,0 +1,222 @@
+use std::io::{Result, Write};
+
+use crate::mir::data::{Instruction, Program};
+
+use super::{
+	layout::{MEMORY_SIZE, MEMORY_START},
+	tab::Tab,
+};
+
+static HEADER: &str = "#ifndef BF_RUN_H
+#define BF_RUN_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+/* Runs the program on a tape of `len` bytes, starting in its middle. The tape
+ * is not cleared first. `in` returns the next byte of input, or -1 at the end,
+ * and `out` is given each byte of output. Both receive `ctx` as is. Returns 0,
+ * or -1 if the program moves off the tape, in which case it stops there. */
+int bf_run(void *ctx, int (*in)(void *), void (*out)(void *, int), uint8_t *tape,
+	size_t len);
+
+#endif";
+
+// The program either runs as `main` on the standard streams, or as a function
+// on a tape and callbacks given by the caller.
+#[derive(Clone, Copy)]
+enum Entry {
+	Main,
+	Library,
+}
+
+fn write_index(w: &mut dyn Write, entry: Entry, pointer: u32, state: Option<u32>) -> Result<()> {
+	match (state, entry) {
+		(Some(state), _) => write!(w, "loc_{pointer} + loc_{state}"),
+		(None, Entry::Main) => write!(w, "{MEMORY_START} + loc_{pointer}"),
+		(None, Entry::Library) => write!(w, "start + loc_{pointer}"),
+	}
+}
+
+// The tape of the caller may be of any size, so the function stops before an
+// access that would leave it. Indexes wrap, so moving left of the tape does
+// too.
+fn write_check(
+	w: &mut dyn Write,
+	tab: Tab,
+	entry: Entry,
+	pointer: u32,
+	state: Option<u32>,
+) -> Result<()> {
+	if let Entry::Library = entry {
+		write!(w, "if ((size_t) (")?;
+		write_index(w, entry, pointer, state)?;
+		writeln!(w, ") >= len) return -1;")?;
+		write!(w, "{tab}")?;
+	}
+
+	Ok(())
+}
+
+fn write_cell(w: &mut dyn Write, entry: Entry, pointer: u32, state: Option<u32>) -> Result<()> {
+	write!(w, "memory[")?;
+	write_index(w, entry, pointer, state)?;
+	write!(w, "]")
+}
+
+#[allow(clippy::cast_possible_truncation)]
+fn write_insn(
+	w: &mut dyn Write,
+	tab: Tab,
+	entry: Entry,
+	bodies: &[Box<[Instruction]>],
+	insn: &Instruction,
+) -> Result<()> {
+	match insn {
+		Instruction::Memory { result } => match entry {
+			Entry::Main => writeln!(w, "loc_{result} = {MEMORY_START};"),
+			Entry::Library => writeln!(w, "loc_{result} = start;"),
+		},
+		Instruction::IO { result } => {
+			writeln!(w, "loc_{result} = 0; /* io state is no-op in C */")
+		}
+		// Registers are 32 bits wide, and wider constants would not be C89.
+		Instruction::Integer { result, value } => {
+			writeln!(w, "loc_{result} = {};", *value as u32)
+		}
+		Instruction::Move { from, to } => writeln!(w, "loc_{to} = loc_{from};"),
+		Instruction::Add { result, lhs, rhs } => {
+			writeln!(w, "loc_{result} = loc_{lhs} + loc_{rhs};")
+		}
+		Instruction::Sub { result, lhs, rhs } => {
+			writeln!(w, "loc_{result} = loc_{lhs} - loc_{rhs};")
+		}
+		Instruction::Load {
+			result,
+			pointer,
+			state,
+		} => {
+			write_check(w, tab, entry, *pointer, *state)?;
+			write!(w, "loc_{result} = ")?;
+			write_cell(w, entry, *pointer, *state)?;
+			writeln!(w, ";")
+		}
+		Instruction::Store {
+			pointer,
+			value,
+			state,
+		} => {
+			write_check(w, tab, entry, *pointer, *state)?;
+			write_cell(w, entry, *pointer, *state)?;
+			writeln!(w, " = loc_{value};")
+		}
+		Instruction::Ask { result, .. } => match entry {
+			Entry::Main => writeln!(w, "loc_{result} = fgetc(stdin);"),
+			Entry::Library => writeln!(w, "loc_{result} = in(ctx);"),
+		},
+		Instruction::Tell { value, .. } => match entry {
+			Entry::Main => writeln!(w, "fputc(loc_{value}, stdout);"),
+			Entry::Library => writeln!(w, "out(ctx, (int) (loc_{value} & 255));"),
+		},
+		Instruction::Select { condition, code } => {
+			let mut iter = code.iter();
+			let last = iter.next_back().unwrap();
+
+			writeln!(w, "switch (loc_{condition}) {{")?;
+
+			for (i, code) in iter.enumerate() {
+				writeln!(w, "{tab}case {i}:")?;
+				write_block(w, tab.add(), entry, bodies, *code)?;
+				writeln!(w, "{tab}break;")?;
+			}
+
+			writeln!(w, "{tab}default:")?;
+			write_block(w, tab.add(), entry, bodies, *last)?;
+			writeln!(w, "{tab}}}")
+		}
+		Instruction::Repeat { code, condition } => {
+			writeln!(w, "do {{")?;
+
+			write_block(w, tab.add(), entry, bodies, *code)?;
+
+			writeln!(w, "{tab}}} while (loc_{condition});")
+		}
+	}
+}
+
+fn write_block(
+	w: &mut dyn Write,
+	tab: Tab,
+	entry: Entry,
+	bodies: &[Box<[Instruction]>],
+	index: usize,
+) -> Result<()> {
+	bodies[index].iter().try_for_each(|insn| {
+		write!(w, "{tab}")?;
+
+		write_insn(w, tab, entry, bodies, insn)
+	})
+}
+
+fn write_entry(w: &mut dyn Write, tab: Tab, entry: Entry, program: &Program) -> Result<()> {
+	match entry {
+		Entry::Main => writeln!(w, "{tab}uint8_t memory[{MEMORY_SIZE}] = {{ 0 }};")?,
+		Entry::Library => {
+			writeln!(w, "{tab}uint8_t *memory = tape;")?;
+			writeln!(w, "{tab}uint32_t start = (uint32_t) (len / 2);")?;
+		}
+	}
+
+	for index in 0..program.locals() {
+		writeln!(w, "{tab}uint32_t loc_{index};")?;
+	}
+
+	write_block(w, tab, entry, program.bodies(), 0)?;
+
+	writeln!(w, "{tab}return 0;")
+}
+
+/// # Errors
+///
+/// Returns an error if the writer fails.
+pub fn write(writer: &mut dyn Write, program: &Program) -> Result<()> {
+	writeln!(writer, "#include <stdint.h>")?;
+	writeln!(writer, "#include <stdio.h>\n")?;
+
+	writeln!(writer, "int main() {{")?;
+
+	write_entry(writer, Tab::new(1), Entry::Main, program)?;
+
+	writeln!(writer, "}}")
+}
+
+/// Writes the program as a reentrant `bf_run` function that reads from and
+/// writes to callbacks, for linking into other programs. Its declaration is
+/// written by [`write_header`].
+///
+/// # Errors
+///
+/// Returns an error if the writer fails.
+pub fn write_library(writer: &mut dyn Write, program: &Program) -> Result<()> {
+	writeln!(writer, "#include <stddef.h>")?;
+	writeln!(writer, "#include <stdint.h>\n")?;
+
+	writeln!(
+		writer,
+		"int bf_run(void *ctx, int (*in)(void *), void (*out)(void *, int), uint8_t *tape,"
+	)?;
+	writeln!(writer, "\tsize_t len) {{")?;
+
+	write_entry(writer, Tab::new(1), Entry::Library, program)?;
+
+	writeln!(writer, "}}")
+}
+
+/// Writes the header that declares the function written by [`write_library`].
+///
+/// # Errors
+///
+/// Returns an error if the writer fails.
+pub fn write_header(writer: &mut dyn Write) -> Result<()> {
+	writeln!(writer, "{HEADER}")
+}
@@ -0,0 +1,463 @@
+use std::io::{Result, Write};
+
+use crate::mir::data::{Instruction, Program};
+
+use super::layout::{MEMORY_SIZE, MEMORY_START};
+
+const TEXT_ADDRESS: u32 = 0x40_0000;
+const BSS_ADDRESS: u32 = 0x60_0000;
+
+const HEADER_SIZE: u16 = 64;
+const PROGRAM_HEADER_SIZE: u16 = 56;
+const PROGRAM_HEADER_COUNT: u16 = 2;
+
+const BUFFER_SIZE: u32 = 4096;
+
+const RAX: u8 = 0;
+const RCX: u8 = 1;
+const RDX: u8 = 2;
+
+/// Where everything lives in the `.bss` segment. Registers of the program
+/// are all kept in memory at fixed addresses, which keeps encoding simple.
+struct Layout {
+	memory: u32,
+	buffer: u32,
+	length: u32,
+	input: u32,
+	locals: u32,
+	size: u32,
+}
+
+impl Layout {
+	fn new(program: &Program) -> Self {
+		let memory = BSS_ADDRESS;
+		let buffer = memory + u32::try_from(MEMORY_SIZE).unwrap();
+		let length = buffer + BUFFER_SIZE;
+		let input = length + 8;
+		let locals = input + 8;
+		let end = locals + u32::try_from(program.locals() * 8).unwrap();
+
+		Self {
+			memory,
+			buffer,
+			length,
+			input,
+			locals,
+			size: end - BSS_ADDRESS,
+		}
+	}
+
+	fn local(&self, register: u32) -> u32 {
+		self.locals + register * 8
+	}
+
+	fn cell(&self) -> u32 {
+		self.memory + u32::try_from(MEMORY_START).unwrap()
+	}
+}
+
+#[derive(Clone, Copy)]
+struct Label(usize);
+
+/// Encodes the few x86-64 instructions needed, patching branch targets
+/// once all labels are placed.
+#[derive(Default)]
+struct Assembler {
+	code: Vec<u8>,
+	labels: Vec<Option<usize>>,
+	fixups: Vec<(usize, Label)>,
+}
+
+impl Assembler {
+	fn bytes(&mut self, bytes: &[u8]) {
+		self.code.extend_from_slice(bytes);
+	}
+
+	fn imm32(&mut self, value: u32) {
+		self.bytes(&value.to_le_bytes());
+	}
+
+	fn new_label(&mut self) -> Label {
+		self.labels.push(None);
+
+		Label(self.labels.len() - 1)
+	}
+
+	fn bind(&mut self, label: Label) {
+		self.labels[label.0] = Some(self.code.len());
+	}
+
+	// Any instruction ending in a 32-bit offset relative to its end.
+	fn relative(&mut self, opcode: &[u8], label: Label) {
+		self.bytes(opcode);
+		self.fixups.push((self.code.len(), label));
+		self.imm32(0);
+	}
+
+	// `op reg, [address]` or `op [address], reg` on 64-bit operands.
+	fn absolute(&mut self, opcode: u8, reg: u8, address: u32) {
+		self.bytes(&[0x48, opcode, 0x04 | reg << 3, 0x25]);
+		self.imm32(address);
+	}
+
+	fn load(&mut self, reg: u8, address: u32) {
+		self.absolute(0x8B, reg, address);
+	}
+
+	fn store(&mut self, reg: u8, address: u32) {
+		self.absolute(0x89, reg, address);
+	}
+
+	// `mov qword [address], imm32` with the immediate sign extended.
+	fn store_immediate(&mut self, address: u32, value: u32) {
+		self.bytes(&[0x48, 0xC7, 0x04, 0x25]);
+		self.imm32(address);
+		self.imm32(value);
+	}
+
+	fn mov_immediate(&mut self, reg: u8, value: u32) {
+		self.bytes(&[0xB8 + reg]);
+		self.imm32(value);
+	}
+
+	fn syscall(&mut self) {
+		self.bytes(&[0x0F, 0x05]);
+	}
+
+	fn ret(&mut self) {
+		self.bytes(&[0xC3]);
+	}
+
+	fn finish(mut self) -> Vec<u8> {
+		for (at, label) in std::mem::take(&mut self.fixups) {
+			let target = self.labels[label.0].unwrap();
+			let offset = i32::try_from(target).unwrap() - i32::try_from(at + 4).unwrap();
+
+			self.code[at..at + 4].copy_from_slice(&offset.to_le_bytes());
+		}
+
+		self.code
+	}
+}
+
+struct Helpers {
+	tell: Label,
+	flush: Label,
+	ask: Label,
+}
+
+struct Encoder {
+	assembler: Assembler,
+	layout: Layout,
+	helpers: Helpers,
+}
+
+impl Encoder {
+	fn new(program: &Program) -> Self {
+		let mut assembler = Assembler::default();
+		let helpers = Helpers {
+			tell: assembler.new_label(),
+			flush: assembler.new_label(),
+			ask: assembler.new_label(),
+		};
+
+		Self {
+			assembler,
+			layout: Layout::new(program),
+			helpers,
+		}
+	}
+
+	fn write_tell(&mut self) {
+		let Self {
+			assembler: a,
+			layout,
+			helpers,
+		} = self;
+
+		a.bind(helpers.tell);
+		a.load(RCX, layout.length);
+		// mov [rcx + buffer], al
+		a.bytes(&[0x88, 0x81]);
+		a.imm32(layout.buffer);
+		// inc rcx
+		a.bytes(&[0x48, 0xFF, 0xC1]);
+		a.store(RCX, layout.length);
+		// cmp rcx, imm32
+		a.bytes(&[0x48, 0x81, 0xF9]);
+		a.imm32(BUFFER_SIZE);
+		a.relative(&[0x0F, 0x84], helpers.flush);
+		a.ret();
+	}
+
+	fn write_flush(&mut self) {
+		let Self {
+			assembler: a,
+			layout,
+			helpers,
+		} = self;
+
+		a.bind(helpers.flush);
+		// write(1, buffer, length)
+		a.mov_immediate(RAX, 1);
+		a.bytes(&[0xBF]);
+		a.imm32(1);
+		a.bytes(&[0xBE]);
+		a.imm32(layout.buffer);
+		a.load(RDX, layout.length);
+		a.syscall();
+		a.store_immediate(layout.length, 0);
+		a.ret();
+	}
+
+	// Gives the byte read in `rax`, or `-1` past the end of the input.
+	fn write_ask(&mut self) {
+		let Self {
+			assembler: a,
+			layout,
+			helpers,
+		} = self;
+
+		let end = a.new_label();
+
+		a.bind(helpers.ask);
+		a.relative(&[0xE8], helpers.flush);
+		// read(0, input, 1)
+		a.bytes(&[0x31, 0xC0, 0x31, 0xFF]);
+		a.bytes(&[0xBE]);
+		a.imm32(layout.input);
+		a.mov_immediate(RDX, 1);
+		a.syscall();
+		// test rax, rax
+		a.bytes(&[0x48, 0x85, 0xC0]);
+		a.relative(&[0x0F, 0x8E], end);
+		// movzx eax, byte [input]
+		a.bytes(&[0x0F, 0xB6, 0x04, 0x25]);
+		a.imm32(layout.input);
+		a.ret();
+		a.bind(end);
+		// mov rax, -1
+		a.bytes(&[0x48, 0xC7, 0xC0]);
+		a.imm32(u32::MAX);
+		a.ret();
+	}
+
+	fn write_exit(&mut self) {
+		let a = &mut self.assembler;
+
+		a.relative(&[0xE8], self.helpers.flush);
+		a.mov_immediate(RAX, 60);
+		a.bytes(&[0x31, 0xFF]);
+		a.syscall();
+	}
+
+	// Leaves the offset of the cell in `rax`, returning the base to add.
+	fn write_address(&mut self, pointer: u32, state: Option<u32>) -> u32 {
+		self.assembler.load(RAX, self.layout.local(pointer));
+
+		match state {
+			Some(state) => {
+				self.assembler.absolute(0x03, RAX, self.layout.local(state));
+
+				self.layout.memory
+			}
+			None => self.layout.cell(),
+		}
+	}
+
+	fn write_integer(&mut self, result: u32, value: u64) {
+		let address = self.layout.local(result);
+		let bytes = value.to_le_bytes();
+		let low = u32::from_le_bytes(bytes[..4].try_into().unwrap());
+
+		// Values that survive sign extension from 32 bits fit an immediate.
+		#[allow(clippy::cast_possible_wrap)]
+		let fits = i32::try_from(value as i64).is_ok();
+
+		if fits {
+			self.assembler.store_immediate(address, low);
+		} else {
+			self.assembler.bytes(&[0x48, 0xB8]);
+			self.assembler.bytes(&bytes);
+			self.assembler.store(RAX, address);
+		}
+	}
+
+	fn write_arithmetic(&mut self, opcode: u8, result: u32, lhs: u32, rhs: u32) {
+		self.assembler.load(RAX, self.layout.local(lhs));
+		self.assembler.absolute(opcode, RAX, self.layout.local(rhs));
+		self.assembler.store(RAX, self.layout.local(result));
+	}
+
+	fn write_select(&mut self, bodies: &[Box<[Instruction]>], condition: u32, code: &[usize]) {
+		let labels: Vec<_> = code.iter().map(|_| self.assembler.new_label()).collect();
+		let done = self.assembler.new_label();
+		let (last, rest) = labels.split_last().unwrap();
+
+		self.assembler.load(RAX, self.layout.local(condition));
+
+		for (i, &label) in rest.iter().enumerate() {
+			// cmp al, imm8
+			self.assembler.bytes(&[0x3C, u8::try_from(i).unwrap()]);
+			self.assembler.relative(&[0x0F, 0x84], label);
+		}
+
+		self.assembler.relative(&[0xE9], *last);
+
+		for (&code, &label) in code.iter().zip(rest) {
+			self.assembler.bind(label);
+			self.write_block(bodies, code);
+			self.assembler.relative(&[0xE9], done);
+		}
+
+		self.assembler.bind(*last);
+		self.write_block(bodies, *code.last().unwrap());
+		self.assembler.bind(done);
+	}
+
+	fn write_repeat(&mut self, bodies: &[Box<[Instruction]>], code: usize, condition: u32) {
+		let start = self.assembler.new_label();
+
+		self.assembler.bind(start);
+		self.write_block(bodies, code);
+		self.assembler.load(RAX, self.layout.local(condition));
+		// test al, al
+		self.assembler.bytes(&[0x84, 0xC0]);
+		self.assembler.relative(&[0x0F, 0x85], start);
+	}
+
+	fn write_insn(&mut self, bodies: &[Box<[Instruction]>], insn: &Instruction) {
+		match *insn {
+			Instruction::Memory { result } => {
+				let start = u32::try_from(MEMORY_START).unwrap();
+
+				self.assembler
+					.store_immediate(self.layout.local(result), start);
+			}
+			Instruction::IO { result } => {
+				self.assembler.store_immediate(self.layout.local(result), 0);
+			}
+			Instruction::Integer { result, value } => self.write_integer(result, value),
+			Instruction::Move { from, to } => {
+				self.assembler.load(RAX, self.layout.local(from));
+				self.assembler.store(RAX, self.layout.local(to));
+			}
+			Instruction::Add { result, lhs, rhs } => self.write_arithmetic(0x03, result, lhs, rhs),
+			Instruction::Sub { result, lhs, rhs } => self.write_arithmetic(0x2B, result, lhs, rhs),
+			Instruction::Load {
+				result,
+				pointer,
+				state,
+			} => {
+				let base = self.write_address(pointer, state);
+
+				// movzx eax, byte [rax + base]
+				self.assembler.bytes(&[0x0F, 0xB6, 0x80]);
+				self.assembler.imm32(base);
+				self.assembler.store(RAX, self.layout.local(result));
+			}
+			Instruction::Store {
+				pointer,
+				value,
+				state,
+			} => {
+				let base = self.write_address(pointer, state);
+
+				self.assembler.load(RCX, self.layout.local(value));
+				// mov [rax + base], cl
+				self.assembler.bytes(&[0x88, 0x88]);
+				self.assembler.imm32(base);
+			}
+			Instruction::Ask { result, .. } => {
+				self.assembler.relative(&[0xE8], self.helpers.ask);
+				self.assembler.store(RAX, self.layout.local(result));
+			}
+			Instruction::Tell { value, .. } => {
+				self.assembler.load(RAX, self.layout.local(value));
+				self.assembler.relative(&[0xE8], self.helpers.tell);
+			}
+			Instruction::Select {
+				condition,
+				ref code,
+			} => self.write_select(bodies, condition, code),
+			Instruction::Repeat { code, condition } => self.write_repeat(bodies, code, condition),
+		}
+	}
+
+	fn write_block(&mut self, bodies: &[Box<[Instruction]>], index: usize) {
+		for insn in bodies[index].iter() {
+			self.write_insn(bodies, insn);
+		}
+	}
+
+	fn encode(mut self, program: &Program) -> (Vec<u8>, Layout) {
+		if !program.bodies().is_empty() {
+			self.write_block(program.bodies(), 0);
+		}
+
+		self.write_exit();
+		self.write_tell();
+		self.write_flush();
+		self.write_ask();
+
+		(self.assembler.finish(), self.layout)
+	}
+}
+
+fn write_program_header(
+	w: &mut dyn Write,
+	flags: u32,
+	address: u32,
+	file_size: u64,
+	memory_size: u64,
+) -> Result<()> {
+	// A loadable segment, aligned to pages.
+	w.write_all(&1_u32.to_le_bytes())?;
+	w.write_all(&flags.to_le_bytes())?;
+	w.write_all(&0_u64.to_le_bytes())?;
+	w.write_all(&u64::from(address).to_le_bytes())?;
+	w.write_all(&u64::from(address).to_le_bytes())?;
+	w.write_all(&file_size.to_le_bytes())?;
+	w.write_all(&memory_size.to_le_bytes())?;
+	w.write_all(&0x1000_u64.to_le_bytes())
+}
+
+fn write_header(w: &mut dyn Write, code: &[u8], layout: &Layout) -> Result<()> {
+	let headers = HEADER_SIZE + PROGRAM_HEADER_SIZE * PROGRAM_HEADER_COUNT;
+	let entry = TEXT_ADDRESS + u32::from(headers);
+	let text_size = u64::from(headers) + u64::try_from(code.len()).unwrap();
+
+	// 64-bit, little endian, version 1, System V.
+	w.write_all(&[0x7F, b'E', b'L', b'F', 2, 1, 1, 0])?;
+	w.write_all(&[0; 8])?;
+	// An executable for x86-64.
+	w.write_all(&2_u16.to_le_bytes())?;
+	w.write_all(&0x3E_u16.to_le_bytes())?;
+	w.write_all(&1_u32.to_le_bytes())?;
+	w.write_all(&u64::from(entry).to_le_bytes())?;
+	w.write_all(&u64::from(HEADER_SIZE).to_le_bytes())?;
+	w.write_all(&0_u64.to_le_bytes())?;
+	w.write_all(&0_u32.to_le_bytes())?;
+	w.write_all(&HEADER_SIZE.to_le_bytes())?;
+	w.write_all(&PROGRAM_HEADER_SIZE.to_le_bytes())?;
+	w.write_all(&PROGRAM_HEADER_COUNT.to_le_bytes())?;
+	w.write_all(&[0; 6])?;
+
+	// The headers and code are mapped as is, the `.bss` is zero filled.
+	write_program_header(w, 5, TEXT_ADDRESS, text_size, text_size)?;
+	write_program_header(w, 6, BSS_ADDRESS, 0, u64::from(layout.size))
+}
+
+/// Writes the program as a static ELF executable for x86-64 Linux, needing
+/// no assembler or linker.
+///
+/// # Errors
+///
+/// Returns an error if the writer fails.
+pub fn write(writer: &mut dyn Write, program: &Program) -> Result<()> {
+	let (code, layout) = Encoder::new(program).encode(program);
+
+	write_header(writer, &code, &layout)?;
+
+	writer.write_all(&code)
+}
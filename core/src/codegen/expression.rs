@@ -0,0 +1,56 @@
+//! Pieces of expressions shared by the targets that write source code in a
+//! language whose numbers do not wrap to a byte on their own.
+
+use std::fmt::{Display, Formatter, Result};
+
+use super::layout::MEMORY_START;
+
+/// The text written before and after a value to wrap it to a byte.
+#[derive(Clone, Copy)]
+pub struct Mask(pub &'static str, pub &'static str);
+
+/// Wraps a value to a byte when the instruction needs it.
+pub struct Wrap<T>(pub T, pub Option<Mask>);
+
+impl<T: Display> Display for Wrap<T> {
+	fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+		match self.1 {
+			Some(Mask(prefix, suffix)) => write!(f, "{prefix}{}{suffix}", self.0),
+			None => self.0.fmt(f),
+		}
+	}
+}
+
+/// A register written as a plain variable.
+pub struct Name(pub u32);
+
+impl Display for Name {
+	fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+		write!(f, "loc_{}", self.0)
+	}
+}
+
+/// A cell of the memory, given its pointer and state registers. The memory
+/// is always the one buffer, so a retained state only holds the offset of
+/// the pointer into it, as in C.
+pub struct Cell<R>(pub R, pub Option<R>);
+
+impl<R: Display> Display for Cell<R> {
+	fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+		match &self.1 {
+			Some(state) => write!(f, "memory[{} + {state}]", self.0),
+			None => write!(f, "memory[{MEMORY_START} + {}]", self.0),
+		}
+	}
+}
+
+/// An integer constant. Constants are written as signed so that pointer
+/// offsets stay small.
+pub struct Constant(pub u64);
+
+impl Display for Constant {
+	#[allow(clippy::cast_possible_wrap)]
+	fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+		write!(f, "{}", self.0 as i64)
+	}
+}
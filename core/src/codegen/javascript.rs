@@ -0,0 +1,152 @@
+use std::io::{Result, Write};
+
+use crate::mir::data::{Instruction, Program};
+
+use super::{
+	expression::{Cell, Constant, Mask, Name, Wrap},
+	layout::{MEMORY_SIZE, MEMORY_START},
+	tab::Tab,
+	wrapping::Wrapping,
+};
+
+static RUN: &str = "export function run(input) {
+	const output = [];
+	let position = 0;
+
+	stream(
+		() => (position < input.length ? input[position++] : -1),
+		(value) => output.push(value),
+	);
+
+	return Uint8Array.from(output);
+}";
+
+const MASK: Mask = Mask("(", " & 255)");
+
+fn write_insn(
+	w: &mut dyn Write,
+	tab: Tab,
+	bodies: &[Box<[Instruction]>],
+	wrapping: &Wrapping,
+	insn: &Instruction,
+	mask: Option<Mask>,
+) -> Result<()> {
+	match insn {
+		Instruction::Memory { result } => writeln!(w, "loc_{result} = {MEMORY_START};"),
+		Instruction::IO { result } => writeln!(w, "loc_{result} = 0;"),
+		Instruction::Integer { result, value } => {
+			writeln!(w, "loc_{result} = {};", Constant(*value))
+		}
+		Instruction::Move { from, to } => writeln!(w, "loc_{to} = loc_{from};"),
+		Instruction::Add { result, lhs, rhs } => {
+			let sum = format!("loc_{lhs} + loc_{rhs}");
+
+			writeln!(w, "loc_{result} = {};", Wrap(sum, mask))
+		}
+		Instruction::Sub { result, lhs, rhs } => {
+			let difference = format!("loc_{lhs} - loc_{rhs}");
+
+			writeln!(w, "loc_{result} = {};", Wrap(difference, mask))
+		}
+		Instruction::Load {
+			result,
+			pointer,
+			state,
+		} => writeln!(
+			w,
+			"loc_{result} = {};",
+			Cell(Name(*pointer), state.map(Name))
+		),
+		Instruction::Store {
+			pointer,
+			value,
+			state,
+		} => {
+			let value = Wrap(Name(*value), mask);
+
+			writeln!(w, "{} = {value};", Cell(Name(*pointer), state.map(Name)))
+		}
+		Instruction::Ask { result, .. } => {
+			writeln!(w, "loc_{result} = {};", Wrap("ask()", mask))
+		}
+		Instruction::Tell { value, .. } => {
+			writeln!(w, "tell({});", Wrap(Name(*value), mask))
+		}
+		Instruction::Select { condition, code } => {
+			let condition = Wrap(Name(*condition), mask);
+			let mut iter = code.iter();
+			let last = iter.next_back().unwrap();
+
+			for (i, code) in iter.enumerate() {
+				writeln!(w, "if ({condition} === {i}) {{")?;
+				write_block(w, tab.add(), bodies, wrapping, *code)?;
+				write!(w, "{tab}}} else ")?;
+			}
+
+			writeln!(w, "{{")?;
+			write_block(w, tab.add(), bodies, wrapping, *last)?;
+			writeln!(w, "{tab}}}")
+		}
+		Instruction::Repeat { code, condition } => {
+			writeln!(w, "do {{")?;
+
+			write_block(w, tab.add(), bodies, wrapping, *code)?;
+
+			let condition = Wrap(Name(*condition), mask);
+
+			writeln!(w, "{tab}}} while ({condition} !== 0);")
+		}
+	}
+}
+
+fn write_block(
+	w: &mut dyn Write,
+	tab: Tab,
+	bodies: &[Box<[Instruction]>],
+	wrapping: &Wrapping,
+	index: usize,
+) -> Result<()> {
+	bodies[index]
+		.iter()
+		.enumerate()
+		.try_for_each(|(position, insn)| {
+			let mask = wrapping.is_masked(index, position).then_some(MASK);
+
+			write!(w, "{tab}")?;
+
+			write_insn(w, tab, bodies, wrapping, insn, mask)
+		})
+}
+
+fn write_stream(w: &mut dyn Write, tab: Tab, program: &Program) -> Result<()> {
+	writeln!(w, "{tab}const memory = new Uint8Array({MEMORY_SIZE});")?;
+
+	for index in 0..program.locals() {
+		writeln!(w, "{tab}let loc_{index} = 0;")?;
+	}
+
+	if program.bodies().is_empty() {
+		return Ok(());
+	}
+
+	let wrapping = Wrapping::new(program);
+
+	write_block(w, tab, program.bodies(), &wrapping, 0)
+}
+
+/// Writes the program as an ES module. It exports `stream(ask, tell)`, which
+/// calls `ask` for each input byte, expecting `-1` at the end, and `tell`
+/// for each output byte, and `run(input)`, which maps a `Uint8Array` of
+/// input to one of output.
+///
+/// # Errors
+///
+/// Returns an error if the writer fails.
+pub fn write(writer: &mut dyn Write, program: &Program) -> Result<()> {
+	writeln!(writer, "export function stream(ask, tell) {{")?;
+
+	write_stream(writer, Tab::new(1), program)?;
+
+	writeln!(writer, "}}\n")?;
+	writeln!(writer, "{RUN}")
+}
@@ -0,0 +1,418 @@
+//! Compiles programs to native code in process with Cranelift. The compiled
+//! function receives the tape and a pair of callbacks for input and output,
+//! so nothing is written to disk and no external compiler is needed.
+
+use std::{ffi::c_void, mem::ManuallyDrop};
+
+use cranelift_codegen::{
+	ir::{types::I64, AbiParam, InstBuilder, MemFlags, Signature, Type, Value},
+	settings::{self, Configurable},
+	CodegenError,
+};
+use cranelift_frontend::{FunctionBuilder, FunctionBuilderContext, Switch, Variable};
+use cranelift_jit::{JITBuilder, JITModule};
+use cranelift_module::{default_libcall_names, Linkage, Module, ModuleError};
+
+use crate::mir::data::{Instruction, Program};
+
+use super::layout::{MEMORY_SIZE, MEMORY_START};
+
+type Ask = extern "C" fn(*mut c_void) -> i64;
+type Tell = extern "C" fn(*mut c_void, i64);
+type Entry = unsafe extern "C" fn(*mut u8, *mut c_void, Ask, Tell);
+
+#[derive(Debug)]
+pub enum JitError {
+	UnsupportedHost(&'static str),
+	Codegen(CodegenError),
+	Module(Box<ModuleError>),
+}
+
+impl From<CodegenError> for JitError {
+	fn from(error: CodegenError) -> Self {
+		Self::Codegen(error)
+	}
+}
+
+impl From<ModuleError> for JitError {
+	fn from(error: ModuleError) -> Self {
+		Self::Module(Box::new(error))
+	}
+}
+
+struct Callbacks<'a> {
+	ask: &'a mut dyn FnMut() -> Option<u8>,
+	tell: &'a mut dyn FnMut(u8),
+}
+
+// Reading past the end of the input gives `-1`, as in the other targets.
+extern "C" fn ask(data: *mut c_void) -> i64 {
+	let callbacks = unsafe { &mut *data.cast::<Callbacks>() };
+
+	(callbacks.ask)().map_or(-1, i64::from)
+}
+
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+extern "C" fn tell(data: *mut c_void, value: i64) {
+	let callbacks = unsafe { &mut *data.cast::<Callbacks>() };
+
+	(callbacks.tell)(value as u8);
+}
+
+struct Parameters {
+	memory: Value,
+	data: Value,
+	ask: Value,
+	tell: Value,
+}
+
+struct Translator<'a> {
+	builder: FunctionBuilder<'a>,
+	parameters: Parameters,
+	pointer_type: Type,
+}
+
+impl Translator<'_> {
+	fn get(&mut self, register: u32) -> Value {
+		self.builder.use_var(Variable::from_u32(register))
+	}
+
+	fn set(&mut self, register: u32, value: Value) {
+		self.builder.def_var(Variable::from_u32(register), value);
+	}
+
+	#[allow(clippy::cast_possible_wrap)]
+	fn set_integer(&mut self, register: u32, value: u64) {
+		let value = self.builder.ins().iconst(I64, value as i64);
+
+		self.set(register, value);
+	}
+
+	// The tape size is a power of two, so masking the offset wraps it around
+	// the tape and no access can leave it.
+	fn get_address(&mut self, pointer: u32, state: Option<u32>) -> Value {
+		let pointer = self.get(pointer);
+		let mut offset = match state {
+			Some(state) => {
+				let state = self.get(state);
+
+				self.builder.ins().iadd(pointer, state)
+			}
+			None => self
+				.builder
+				.ins()
+				.iadd_imm(pointer, i64::try_from(MEMORY_START).unwrap()),
+		};
+
+		offset = self
+			.builder
+			.ins()
+			.band_imm(offset, i64::try_from(MEMORY_SIZE - 1).unwrap());
+
+		if self.pointer_type != I64 {
+			offset = self.builder.ins().ireduce(self.pointer_type, offset);
+		}
+
+		self.builder.ins().iadd(self.parameters.memory, offset)
+	}
+
+	fn get_condition(&mut self, condition: u32) -> Value {
+		let condition = self.get(condition);
+
+		self.builder.ins().band_imm(condition, 0xFF)
+	}
+
+	fn add_select(&mut self, bodies: &[Box<[Instruction]>], condition: u32, code: &[usize]) {
+		let blocks: Vec<_> = code.iter().map(|_| self.builder.create_block()).collect();
+		let done = self.builder.create_block();
+		let (last, rest) = blocks.split_last().unwrap();
+		let condition = self.get_condition(condition);
+		let mut switch = Switch::new();
+
+		for (i, &block) in rest.iter().enumerate() {
+			switch.set_entry(i as u128, block);
+		}
+
+		switch.emit(&mut self.builder, condition, *last);
+
+		for (&block, &code) in blocks.iter().zip(code) {
+			self.builder.switch_to_block(block);
+			self.builder.seal_block(block);
+			self.add_block(bodies, code);
+			self.builder.ins().jump(done, &[]);
+		}
+
+		self.builder.switch_to_block(done);
+		self.builder.seal_block(done);
+	}
+
+	fn add_repeat(&mut self, bodies: &[Box<[Instruction]>], code: usize, condition: u32) {
+		let body = self.builder.create_block();
+		let done = self.builder.create_block();
+
+		self.builder.ins().jump(body, &[]);
+		self.builder.switch_to_block(body);
+		self.add_block(bodies, code);
+
+		let condition = self.get_condition(condition);
+
+		self.builder.ins().brif(condition, body, &[], done, &[]);
+		self.builder.seal_block(body);
+		self.builder.switch_to_block(done);
+		self.builder.seal_block(done);
+	}
+
+	fn add_call(&mut self, callee: Value, arguments: &[Value], result: bool) -> Option<Value> {
+		let mut signature = Signature::new(self.builder.func.signature.call_conv);
+
+		signature.params.push(AbiParam::new(self.pointer_type));
+		signature
+			.params
+			.extend(arguments.iter().map(|_| AbiParam::new(I64)));
+
+		if result {
+			signature.returns.push(AbiParam::new(I64));
+		}
+
+		let signature = self.builder.import_signature(signature);
+		let mut list = vec![self.parameters.data];
+
+		list.extend_from_slice(arguments);
+
+		let call = self.builder.ins().call_indirect(signature, callee, &list);
+
+		self.builder.inst_results(call).first().copied()
+	}
+
+	fn add_insn(&mut self, bodies: &[Box<[Instruction]>], insn: &Instruction) {
+		match *insn {
+			Instruction::Memory { result } => {
+				self.set_integer(result, MEMORY_START.try_into().unwrap());
+			}
+			Instruction::IO { result } => self.set_integer(result, 0),
+			Instruction::Integer { result, value } => self.set_integer(result, value),
+			Instruction::Move { from, to } => {
+				let value = self.get(from);
+
+				self.set(to, value);
+			}
+			Instruction::Add { result, lhs, rhs } => {
+				let lhs = self.get(lhs);
+				let rhs = self.get(rhs);
+				let value = self.builder.ins().iadd(lhs, rhs);
+
+				self.set(result, value);
+			}
+			Instruction::Sub { result, lhs, rhs } => {
+				let lhs = self.get(lhs);
+				let rhs = self.get(rhs);
+				let value = self.builder.ins().isub(lhs, rhs);
+
+				self.set(result, value);
+			}
+			Instruction::Load {
+				result,
+				pointer,
+				state,
+			} => {
+				let address = self.get_address(pointer, state);
+				let value = self.builder.ins().uload8(I64, MemFlags::new(), address, 0);
+
+				self.set(result, value);
+			}
+			Instruction::Store {
+				pointer,
+				value,
+				state,
+			} => {
+				let address = self.get_address(pointer, state);
+				let value = self.get(value);
+
+				self.builder
+					.ins()
+					.istore8(MemFlags::new(), value, address, 0);
+			}
+			Instruction::Ask { result, .. } => {
+				let value = self.add_call(self.parameters.ask, &[], true).unwrap();
+
+				self.set(result, value);
+			}
+			Instruction::Tell { value, .. } => {
+				let value = self.get(value);
+
+				self.add_call(self.parameters.tell, &[value], false);
+			}
+			Instruction::Select {
+				condition,
+				ref code,
+			} => self.add_select(bodies, condition, code),
+			Instruction::Repeat { code, condition } => self.add_repeat(bodies, code, condition),
+		}
+	}
+
+	fn add_block(&mut self, bodies: &[Box<[Instruction]>], index: usize) {
+		for insn in &bodies[index] {
+			self.add_insn(bodies, insn);
+		}
+	}
+}
+
+fn new_module() -> Result<JITModule, JitError> {
+	let mut flags = settings::builder();
+
+	flags.set("opt_level", "speed").unwrap();
+
+	let isa = cranelift_native::builder()
+		.map_err(JitError::UnsupportedHost)?
+		.finish(settings::Flags::new(flags))?;
+
+	Ok(JITModule::new(JITBuilder::with_isa(
+		isa,
+		default_libcall_names(),
+	)))
+}
+
+fn translate(module: &mut JITModule, program: &Program) -> Result<*const u8, JitError> {
+	let pointer_type = module.target_config().pointer_type();
+	let mut signature = module.make_signature();
+
+	signature.params.extend([AbiParam::new(pointer_type); 4]);
+
+	let id = module.declare_function("main", Linkage::Export, &signature)?;
+	let mut context = module.make_context();
+	let mut function_context = FunctionBuilderContext::new();
+
+	context.func.signature = signature;
+
+	let mut builder = FunctionBuilder::new(&mut context.func, &mut function_context);
+	let entry = builder.create_block();
+
+	builder.append_block_params_for_function_params(entry);
+	builder.switch_to_block(entry);
+	builder.seal_block(entry);
+
+	for register in 0..program.locals() {
+		let variable = Variable::from_u32(register.try_into().unwrap());
+
+		builder.declare_var(variable, I64);
+
+		let zero = builder.ins().iconst(I64, 0);
+
+		builder.def_var(variable, zero);
+	}
+
+	let parameters = match *builder.block_params(entry) {
+		[memory, data, ask, tell] => Parameters {
+			memory,
+			data,
+			ask,
+			tell,
+		},
+		_ => unreachable!(),
+	};
+
+	let mut translator = Translator {
+		builder,
+		parameters,
+		pointer_type,
+	};
+
+	if !program.bodies().is_empty() {
+		translator.add_block(program.bodies(), 0);
+	}
+
+	translator.builder.ins().return_(&[]);
+	translator.builder.finalize();
+
+	module.define_function(id, &mut context)?;
+	module.clear_context(&mut context);
+	module.finalize_definitions()?;
+
+	Ok(module.get_finalized_function(id))
+}
+
+/// A program compiled to native code, which can be run any number of times.
+pub struct Compiled {
+	module: ManuallyDrop<JITModule>,
+	entry: Entry,
+}
+
+impl Compiled {
+	/// Runs the program on a fresh tape, reading input from `ask` until it
+	/// returns [`None`] and writing output to `tell`. The tape wraps around
+	/// at both ends.
+	pub fn run(&self, ask: &mut dyn FnMut() -> Option<u8>, tell: &mut dyn FnMut(u8)) {
+		let mut memory = vec![0_u8; MEMORY_SIZE];
+		let mut callbacks = Callbacks { ask, tell };
+		let data = std::ptr::from_mut(&mut callbacks);
+
+		unsafe {
+			(self.entry)(memory.as_mut_ptr(), data.cast(), self::ask, self::tell);
+		}
+	}
+}
+
+impl Drop for Compiled {
+	fn drop(&mut self) {
+		let module = unsafe { ManuallyDrop::take(&mut self.module) };
+
+		unsafe { module.free_memory() };
+	}
+}
+
+/// Compiles the program to native code for the host.
+///
+/// # Errors
+///
+/// Returns an error if the host is not supported by Cranelift or the
+/// program fails to compile.
+pub fn compile(program: &Program) -> Result<Compiled, JitError> {
+	let mut module = new_module()?;
+	let code = translate(&mut module, program)?;
+	let entry = unsafe { std::mem::transmute::<*const u8, Entry>(code) };
+
+	Ok(Compiled {
+		module: ManuallyDrop::new(module),
+		entry,
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::mir::text::Reader;
+
+	use super::compile;
+
+	// Walks right past the end of the tape, then left past its start.
+	static WALK: &str = "locals 5
+r0 = integer 0
+r1 = integer 100
+r2 = integer 255
+r3 = integer 1
+repeat {
+	store r0, r3
+	r0 = add r0, r1
+	r2 = sub r2, r3
+} while r2
+r2 = integer 255
+repeat {
+	r0 = sub r0, r1
+	r0 = sub r0, r1
+	store r0, r3
+	r2 = sub r2, r3
+} while r2
+r4 = load r0
+tell r4
+";
+
+	#[test]
+	fn wraps_around_tape() {
+		let program = Reader::new().read(WALK).unwrap();
+		let compiled = compile(&program).unwrap();
+		let mut output = Vec::new();
+
+		compiled.run(&mut || None, &mut |value| output.push(value));
+
+		assert_eq!(output, [1]);
+	}
+}
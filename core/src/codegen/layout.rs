@@ -0,0 +1,9 @@
+//! The tape layout shared by targets with a fixed size memory. The pointer
+//! starts in the middle so that programs may move left of where they began.
+//! Without a state register, pointers are offsets from `MEMORY_START`.
+//!
+//! Registers may hold values that are not yet wrapped to a byte, so targets
+//! only test the low byte of a condition, as cells are bytes.
+
+pub const MEMORY_SIZE: usize = 8192;
+pub const MEMORY_START: usize = MEMORY_SIZE / 2;
@@ -0,0 +1,237 @@
+use std::io::{Result, Write};
+
+use crate::mir::data::{Instruction, Program};
+
+use super::layout::{MEMORY_SIZE, MEMORY_START};
+
+// Registers live in stack slots that `mem2reg` turns into SSA values, which
+// keeps the output simple as no phi nodes are needed across regions.
+struct Function<'a> {
+	w: &'a mut dyn Write,
+	temporaries: usize,
+}
+
+impl<'a> Function<'a> {
+	fn new(w: &'a mut dyn Write) -> Self {
+		Self { w, temporaries: 0 }
+	}
+
+	fn next_temporary(&mut self) -> usize {
+		self.temporaries += 1;
+		self.temporaries
+	}
+
+	fn write_get(&mut self, register: u32) -> Result<usize> {
+		let temporary = self.next_temporary();
+
+		writeln!(self.w, "\t%t{temporary} = load i64, ptr %r{register}")?;
+
+		Ok(temporary)
+	}
+
+	fn write_set(&mut self, register: u32, value: &str) -> Result<()> {
+		writeln!(self.w, "\tstore i64 {value}, ptr %r{register}")
+	}
+
+	fn write_arithmetic(&mut self, name: &str, result: u32, lhs: u32, rhs: u32) -> Result<()> {
+		let lhs = self.write_get(lhs)?;
+		let rhs = self.write_get(rhs)?;
+		let temporary = self.next_temporary();
+
+		writeln!(self.w, "\t%t{temporary} = {name} i64 %t{lhs}, %t{rhs}")?;
+
+		self.write_set(result, &format!("%t{temporary}"))
+	}
+
+	// Returns the temporary holding the address of the cell.
+	fn write_address(&mut self, pointer: u32, state: Option<u32>) -> Result<usize> {
+		let pointer = self.write_get(pointer)?;
+		let offset = self.next_temporary();
+
+		match state {
+			Some(state) => {
+				let state = self.write_get(state)?;
+
+				writeln!(self.w, "\t%t{offset} = add i64 %t{pointer}, %t{state}")?;
+			}
+			None => writeln!(self.w, "\t%t{offset} = add i64 %t{pointer}, {MEMORY_START}")?,
+		}
+
+		let address = self.next_temporary();
+
+		writeln!(
+			self.w,
+			"\t%t{address} = getelementptr i8, ptr @memory, i64 %t{offset}"
+		)?;
+
+		Ok(address)
+	}
+
+	fn write_condition(&mut self, condition: u32) -> Result<usize> {
+		let condition = self.write_get(condition)?;
+		let temporary = self.next_temporary();
+
+		writeln!(self.w, "\t%t{temporary} = and i64 %t{condition}, 255")?;
+
+		Ok(temporary)
+	}
+
+	fn write_select(
+		&mut self,
+		bodies: &[Box<[Instruction]>],
+		condition: u32,
+		code: &[usize],
+	) -> Result<()> {
+		let (last, rest) = code.split_last().unwrap();
+		let condition = self.write_condition(condition)?;
+
+		write!(self.w, "\tswitch i64 %t{condition}, label %body{last} [")?;
+
+		for (i, code) in rest.iter().enumerate() {
+			write!(self.w, " i64 {i}, label %body{code}")?;
+		}
+
+		writeln!(self.w, " ]")?;
+
+		for &code in code {
+			writeln!(self.w, "body{code}:")?;
+
+			self.write_block(bodies, code)?;
+
+			writeln!(self.w, "\tbr label %done{last}")?;
+		}
+
+		writeln!(self.w, "done{last}:")
+	}
+
+	fn write_repeat(
+		&mut self,
+		bodies: &[Box<[Instruction]>],
+		code: usize,
+		condition: u32,
+	) -> Result<()> {
+		writeln!(self.w, "\tbr label %body{code}")?;
+		writeln!(self.w, "body{code}:")?;
+
+		self.write_block(bodies, code)?;
+
+		let condition = self.write_condition(condition)?;
+		let test = self.next_temporary();
+
+		writeln!(self.w, "\t%t{test} = icmp ne i64 %t{condition}, 0")?;
+		writeln!(
+			self.w,
+			"\tbr i1 %t{test}, label %body{code}, label %done{code}"
+		)?;
+		writeln!(self.w, "done{code}:")
+	}
+
+	#[allow(clippy::cast_possible_wrap)]
+	fn write_insn(&mut self, bodies: &[Box<[Instruction]>], insn: &Instruction) -> Result<()> {
+		match *insn {
+			Instruction::Memory { result } => self.write_set(result, &MEMORY_START.to_string()),
+			Instruction::IO { result } => self.write_set(result, "0"),
+			Instruction::Integer { result, value } => {
+				self.write_set(result, &(value as i64).to_string())
+			}
+			Instruction::Move { from, to } => {
+				let value = self.write_get(from)?;
+
+				self.write_set(to, &format!("%t{value}"))
+			}
+			Instruction::Add { result, lhs, rhs } => self.write_arithmetic("add", result, lhs, rhs),
+			Instruction::Sub { result, lhs, rhs } => self.write_arithmetic("sub", result, lhs, rhs),
+			Instruction::Load {
+				result,
+				pointer,
+				state,
+			} => {
+				let address = self.write_address(pointer, state)?;
+				let byte = self.next_temporary();
+				let value = self.next_temporary();
+
+				writeln!(self.w, "\t%t{byte} = load i8, ptr %t{address}")?;
+				writeln!(self.w, "\t%t{value} = zext i8 %t{byte} to i64")?;
+
+				self.write_set(result, &format!("%t{value}"))
+			}
+			Instruction::Store {
+				pointer,
+				value,
+				state,
+			} => {
+				let address = self.write_address(pointer, state)?;
+				let value = self.write_get(value)?;
+				let byte = self.next_temporary();
+
+				writeln!(self.w, "\t%t{byte} = trunc i64 %t{value} to i8")?;
+				writeln!(self.w, "\tstore i8 %t{byte}, ptr %t{address}")
+			}
+			// Reading past the end of the input gives `EOF`, which is `-1`.
+			Instruction::Ask { result, .. } => {
+				let character = self.next_temporary();
+				let value = self.next_temporary();
+
+				writeln!(self.w, "\t%t{character} = call i32 @getchar()")?;
+				writeln!(self.w, "\t%t{value} = sext i32 %t{character} to i64")?;
+
+				self.write_set(result, &format!("%t{value}"))
+			}
+			Instruction::Tell { value, .. } => {
+				let value = self.write_get(value)?;
+				let character = self.next_temporary();
+				let ignored = self.next_temporary();
+
+				writeln!(self.w, "\t%t{character} = trunc i64 %t{value} to i32")?;
+				writeln!(
+					self.w,
+					"\t%t{ignored} = call i32 @putchar(i32 %t{character})"
+				)
+			}
+			Instruction::Select {
+				condition,
+				ref code,
+			} => self.write_select(bodies, condition, code),
+			Instruction::Repeat { code, condition } => self.write_repeat(bodies, code, condition),
+		}
+	}
+
+	fn write_block(&mut self, bodies: &[Box<[Instruction]>], index: usize) -> Result<()> {
+		bodies[index]
+			.iter()
+			.try_for_each(|insn| self.write_insn(bodies, insn))
+	}
+}
+
+/// Writes the program as textual LLVM IR, with opaque pointers, to be
+/// compiled with `clang` or optimized with `opt`.
+///
+/// # Errors
+///
+/// Returns an error if the writer fails.
+pub fn write(writer: &mut dyn Write, program: &Program) -> Result<()> {
+	writeln!(
+		writer,
+		"@memory = internal global [{MEMORY_SIZE} x i8] zeroinitializer"
+	)?;
+	writeln!(writer)?;
+	writeln!(writer, "declare i32 @getchar()")?;
+	writeln!(writer, "declare i32 @putchar(i32)")?;
+	writeln!(writer)?;
+	writeln!(writer, "define i32 @main() {{")?;
+	writeln!(writer, "entry:")?;
+
+	for register in 0..program.locals() {
+		writeln!(writer, "\t%r{register} = alloca i64")?;
+		writeln!(writer, "\tstore i64 0, ptr %r{register}")?;
+	}
+
+	let mut function = Function::new(writer);
+
+	if !program.bodies().is_empty() {
+		function.write_block(program.bodies(), 0)?;
+	}
+
+	writeln!(writer, "\tret i32 0")?;
+	writeln!(writer, "}}")
+}
@@ -0,0 +1,205 @@
+use std::{
+	fmt::{Display, Formatter},
+	io::{Result, Write},
+};
+
+use crate::mir::data::{Instruction, Program};
+
+use super::{
+	expression::{Constant, Mask, Wrap},
+	tab::Tab,
+	wrapping::Wrapping,
+};
+
+static MEMORY: &str = "setmetatable({}, { __index = function() return 0 end })";
+static IO: &str = "{ tell = function(n) io.write(string.char(n)) end, ask = function() local c = io.read(1) return c and string.byte(c) or -1 end }";
+
+// Lua allows at most 200 locals in a function, so registers past this
+// limit live in a table instead. Room is left for the table itself and for
+// the locals declared before it, of which `LuaJIT` has the most with 8.
+const LOCAL_LIMIT: u32 = 200 - 1 - 8;
+
+pub(super) struct Local(pub(super) u32);
+
+impl Display for Local {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		if self.0 < LOCAL_LIMIT {
+			write!(f, "loc_{}", self.0)
+		} else {
+			write!(f, "spill[{}]", self.0 - LOCAL_LIMIT)
+		}
+	}
+}
+
+// Programs sequenced without states use a shared memory and IO object.
+enum StateName {
+	Memory(Option<u32>),
+	IO(Option<u32>),
+}
+
+impl Display for StateName {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		match *self {
+			Self::Memory(Some(state)) | Self::IO(Some(state)) => Local(state).fmt(f),
+			Self::Memory(None) => write!(f, "memory"),
+			Self::IO(None) => write!(f, "stdio"),
+		}
+	}
+}
+
+/// The parts of the code that differ between Lua dialects, given the
+/// registers involved. Everything else is written the same way.
+pub(super) struct Dialect {
+	pub(super) mask: Mask,
+	pub(super) memory: fn() -> String,
+	pub(super) io: fn() -> String,
+	pub(super) cell: fn(u32, Option<u32>) -> String,
+	pub(super) ask: fn(Option<u32>) -> String,
+	pub(super) tell: fn(Option<u32>) -> String,
+}
+
+const DIALECT: Dialect = Dialect {
+	mask: Mask("(", ") % 256"),
+	memory: || MEMORY.to_string(),
+	io: || IO.to_string(),
+	cell: |pointer, state| format!("{}[{}]", StateName::Memory(state), Local(pointer)),
+	ask: |state| format!("{}.ask()", StateName::IO(state)),
+	tell: |state| format!("{}.tell", StateName::IO(state)),
+};
+
+fn write_insn(
+	w: &mut dyn Write,
+	tab: Tab,
+	bodies: &[Box<[Instruction]>],
+	wrapping: &Wrapping,
+	dialect: &Dialect,
+	insn: &Instruction,
+	mask: Option<Mask>,
+) -> Result<()> {
+	match insn {
+		Instruction::Memory { result } => {
+			writeln!(w, "{} = {}", Local(*result), (dialect.memory)())
+		}
+		Instruction::IO { result } => writeln!(w, "{} = {}", Local(*result), (dialect.io)()),
+		Instruction::Integer { result, value } => {
+			writeln!(w, "{} = {}", Local(*result), Constant(*value))
+		}
+		Instruction::Move { from, to } => writeln!(w, "{} = {}", Local(*to), Local(*from)),
+		Instruction::Add { result, lhs, rhs } => {
+			let sum = format!("{} + {}", Local(*lhs), Local(*rhs));
+
+			writeln!(w, "{} = {}", Local(*result), Wrap(sum, mask))
+		}
+		Instruction::Sub { result, lhs, rhs } => {
+			let difference = format!("{} - {}", Local(*lhs), Local(*rhs));
+
+			writeln!(w, "{} = {}", Local(*result), Wrap(difference, mask))
+		}
+		Instruction::Load {
+			result,
+			pointer,
+			state,
+		} => writeln!(
+			w,
+			"{} = {}",
+			Local(*result),
+			(dialect.cell)(*pointer, *state)
+		),
+		Instruction::Store {
+			pointer,
+			value,
+			state,
+		} => {
+			let value = Wrap(Local(*value), mask);
+
+			writeln!(w, "{} = {value}", (dialect.cell)(*pointer, *state))
+		}
+		Instruction::Ask { result, state } => {
+			let value = Wrap((dialect.ask)(*state), mask);
+
+			writeln!(w, "{} = {value}", Local(*result))
+		}
+		Instruction::Tell { value, state } => {
+			let tell = (dialect.tell)(*state);
+
+			writeln!(w, "{tell}({})", Wrap(Local(*value), mask))
+		}
+		Instruction::Select { condition, code } => {
+			let condition = Wrap(Local(*condition), mask);
+			let mut iter = code.iter();
+			let last = iter.next_back().unwrap();
+
+			for (i, code) in iter.enumerate() {
+				writeln!(w, "if {condition} == {i} then")?;
+				write_block(w, tab.add(), bodies, wrapping, dialect, *code)?;
+				write!(w, "{tab}else")?;
+			}
+
+			writeln!(w)?;
+			write_block(w, tab.add(), bodies, wrapping, dialect, *last)?;
+			writeln!(w, "{tab}end")
+		}
+		Instruction::Repeat { code, condition } => {
+			writeln!(w, "repeat")?;
+
+			write_block(w, tab.add(), bodies, wrapping, dialect, *code)?;
+
+			writeln!(w, "{tab}until {} == 0", Wrap(Local(*condition), mask))
+		}
+	}
+}
+
+pub(super) fn write_block(
+	w: &mut dyn Write,
+	tab: Tab,
+	bodies: &[Box<[Instruction]>],
+	wrapping: &Wrapping,
+	dialect: &Dialect,
+	index: usize,
+) -> Result<()> {
+	bodies[index]
+		.iter()
+		.enumerate()
+		.try_for_each(|(position, insn)| {
+			let mask = wrapping.is_masked(index, position).then_some(dialect.mask);
+
+			write!(w, "{tab}")?;
+
+			write_insn(w, tab, bodies, wrapping, dialect, insn, mask)
+		})
+}
+
+pub(super) fn write_locals(w: &mut dyn Write, program: &Program) -> Result<()> {
+	let limit = usize::try_from(LOCAL_LIMIT).unwrap();
+
+	for index in 0..program.locals().min(limit) {
+		writeln!(w, "local loc_{index}")?;
+	}
+
+	if program.locals() > limit {
+		writeln!(w, "local spill = {{}}")?;
+	}
+
+	Ok(())
+}
+
+/// # Errors
+///
+/// Returns an error if the writer fails.
+pub fn write(writer: &mut dyn Write, program: &Program) -> Result<()> {
+	writeln!(writer, "local memory = {MEMORY}")?;
+	writeln!(writer, "local stdio = {IO}")?;
+
+	write_locals(writer, program)?;
+
+	let wrapping = Wrapping::new(program);
+
+	write_block(
+		writer,
+		Tab::new(0),
+		program.bodies(),
+		&wrapping,
+		&DIALECT,
+		0,
+	)
+}
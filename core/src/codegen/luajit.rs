@@ -0,0 +1,87 @@
+use std::io::{Result, Write};
+
+use crate::mir::data::Program;
+
+use super::{
+	expression::{Cell, Mask},
+	layout::{MEMORY_SIZE, MEMORY_START},
+	lua51::{write_block, write_locals, Dialect, Local},
+	tab::Tab,
+	wrapping::Wrapping,
+};
+
+static PRELUDE: &str = r#"local ffi = require("ffi")
+local bit = require("bit")
+local buffer, count = {}, 0
+local function flush() io.write(table.concat(buffer, "", 1, count)) count = 0 end
+local function tell(n) count = count + 1 buffer[count] = string.char(n) if count == 4096 then flush() end end
+local function ask() flush() local c = io.read(1) return c and string.byte(c) or -1 end"#;
+
+const DIALECT: Dialect = Dialect {
+	mask: Mask("bit.band(", ", 255)"),
+	memory: || MEMORY_START.to_string(),
+	io: || "0".to_string(),
+	cell: |pointer, state| Cell(Local(pointer), state.map(Local)).to_string(),
+	ask: |_| "ask()".to_string(),
+	tell: |_| "tell".to_string(),
+};
+
+/// Writes the program for `LuaJIT`, with the memory in an FFI buffer and
+/// output collected in a table that is flushed in large chunks.
+///
+/// # Errors
+///
+/// Returns an error if the writer fails.
+pub fn write(writer: &mut dyn Write, program: &Program) -> Result<()> {
+	writeln!(writer, "{PRELUDE}")?;
+	writeln!(writer, "local memory = ffi.new(\"uint8_t[{MEMORY_SIZE}]\")")?;
+
+	write_locals(writer, program)?;
+
+	let wrapping = Wrapping::new(program);
+
+	write_block(
+		writer,
+		Tab::new(0),
+		program.bodies(),
+		&wrapping,
+		&DIALECT,
+		0,
+	)?;
+
+	writeln!(writer, "flush()")
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::mir::data::{Instruction, Program};
+
+	use super::write;
+
+	// Counts the names declared by `local` statements in the main chunk.
+	fn count_locals(code: &str) -> usize {
+		code.lines()
+			.filter_map(|line| line.strip_prefix("local "))
+			.map(|line| match line.strip_prefix("function ") {
+				Some(_) => 1,
+				None => line.split('=').next().unwrap().split(',').count(),
+			})
+			.sum()
+	}
+
+	#[test]
+	fn fits_local_limit() {
+		let body: Box<[_]> = (0..300)
+			.map(|result| Instruction::Integer { result, value: 0 })
+			.collect();
+		let program = Program::new(Box::new([body]), 300);
+		let mut output = Vec::new();
+
+		write(&mut output, &program).unwrap();
+
+		let code = String::from_utf8(output).unwrap();
+
+		assert!(code.contains("spill[99] = 0"));
+		assert!(count_locals(&code) <= 200);
+	}
+}
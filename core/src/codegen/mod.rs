@@ -0,0 +1,20 @@
+mod expression;
+mod layout;
+mod tab;
+mod usage;
+mod wrapping;
+
+pub mod brainfxck;
+pub mod c89;
+pub mod elf;
+pub mod javascript;
+#[cfg(feature = "jit")]
+pub mod jit;
+pub mod llvm;
+pub mod lua51;
+pub mod luajit;
+pub mod python;
+pub mod qbe;
+pub mod rust;
+pub mod wasm;
+pub mod x86_64;
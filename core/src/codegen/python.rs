@@ -0,0 +1,191 @@
+use std::io::{Result, Write};
+
+use crate::mir::data::{Instruction, Program};
+
+use super::{
+	expression::{Cell, Constant, Mask, Name, Wrap},
+	layout::{MEMORY_SIZE, MEMORY_START},
+	tab::Tab,
+	usage::is_written_first,
+	wrapping::Wrapping,
+};
+
+static PRELUDE: &str = "import sys
+
+
+def ask():
+	data = sys.stdin.buffer.read(1)
+	return data[0] if data else -1
+
+
+def tell(value):
+	sys.stdout.buffer.write(bytes((value,)))";
+
+const MASK: Mask = Mask("(", " & 255)");
+
+// Python has no `do ... while`, so loops are rotated into a `while` whose
+// condition is seeded to pass on entry. Where the body still needs the old
+// value of the condition the loop breaks out at the end instead.
+fn write_repeat(
+	w: &mut dyn Write,
+	tab: Tab,
+	bodies: &[Box<[Instruction]>],
+	wrapping: &Wrapping,
+	code: usize,
+	register: u32,
+	mask: Option<Mask>,
+) -> Result<()> {
+	let condition = Wrap(Name(register), mask);
+
+	if is_written_first(bodies, code, register) {
+		writeln!(w, "loc_{register} = 1")?;
+		writeln!(w, "{tab}while {condition} != 0:")?;
+
+		write_block(w, tab.add(), bodies, wrapping, code)
+	} else {
+		writeln!(w, "while True:")?;
+
+		write_block(w, tab.add(), bodies, wrapping, code)?;
+
+		writeln!(w, "{tab}\tif {condition} == 0:")?;
+		writeln!(w, "{tab}\t\tbreak")
+	}
+}
+
+fn write_insn(
+	w: &mut dyn Write,
+	tab: Tab,
+	bodies: &[Box<[Instruction]>],
+	wrapping: &Wrapping,
+	insn: &Instruction,
+	mask: Option<Mask>,
+) -> Result<()> {
+	match insn {
+		Instruction::Memory { result } => writeln!(w, "loc_{result} = {MEMORY_START}"),
+		Instruction::IO { result } => writeln!(w, "loc_{result} = 0"),
+		Instruction::Integer { result, value } => {
+			writeln!(w, "loc_{result} = {}", Constant(*value))
+		}
+		Instruction::Move { from, to } => writeln!(w, "loc_{to} = loc_{from}"),
+		Instruction::Add { result, lhs, rhs } => {
+			let sum = format!("loc_{lhs} + loc_{rhs}");
+
+			writeln!(w, "loc_{result} = {}", Wrap(sum, mask))
+		}
+		Instruction::Sub { result, lhs, rhs } => {
+			let difference = format!("loc_{lhs} - loc_{rhs}");
+
+			writeln!(w, "loc_{result} = {}", Wrap(difference, mask))
+		}
+		Instruction::Load {
+			result,
+			pointer,
+			state,
+		} => writeln!(
+			w,
+			"loc_{result} = {}",
+			Cell(Name(*pointer), state.map(Name))
+		),
+		Instruction::Store {
+			pointer,
+			value,
+			state,
+		} => {
+			let value = Wrap(Name(*value), mask);
+
+			writeln!(w, "{} = {value}", Cell(Name(*pointer), state.map(Name)))
+		}
+		Instruction::Ask { result, .. } => {
+			writeln!(w, "loc_{result} = {}", Wrap("ask()", mask))
+		}
+		Instruction::Tell { value, .. } => {
+			writeln!(w, "tell({})", Wrap(Name(*value), mask))
+		}
+		Instruction::Select { condition, code } => {
+			let condition = Wrap(Name(*condition), mask);
+			let mut iter = code.iter();
+			let last = iter.next_back().unwrap();
+
+			for (i, code) in iter.enumerate() {
+				let keyword = if i == 0 { "if" } else { "elif" };
+
+				writeln!(w, "{keyword} {condition} == {i}:")?;
+				write_block(w, tab.add(), bodies, wrapping, *code)?;
+				write!(w, "{tab}")?;
+			}
+
+			if code.len() == 1 {
+				writeln!(w)?;
+
+				return write_block(w, tab, bodies, wrapping, *last);
+			}
+
+			writeln!(w, "else:")?;
+			write_block(w, tab.add(), bodies, wrapping, *last)
+		}
+		Instruction::Repeat { code, condition } => {
+			write_repeat(w, tab, bodies, wrapping, *code, *condition, mask)
+		}
+	}
+}
+
+// Python does not allow empty blocks, so those get a `pass`.
+fn write_block(
+	w: &mut dyn Write,
+	tab: Tab,
+	bodies: &[Box<[Instruction]>],
+	wrapping: &Wrapping,
+	index: usize,
+) -> Result<()> {
+	if bodies[index].is_empty() {
+		return writeln!(w, "{tab}pass");
+	}
+
+	bodies[index]
+		.iter()
+		.enumerate()
+		.try_for_each(|(position, insn)| {
+			let mask = wrapping.is_masked(index, position).then_some(MASK);
+
+			write!(w, "{tab}")?;
+
+			write_insn(w, tab, bodies, wrapping, insn, mask)
+		})
+}
+
+fn write_main(w: &mut dyn Write, tab: Tab, program: &Program) -> Result<()> {
+	writeln!(w, "{tab}memory = bytearray({MEMORY_SIZE})")?;
+
+	for index in 0..program.locals() {
+		writeln!(w, "{tab}loc_{index} = 0")?;
+	}
+
+	if program.bodies().is_empty() {
+		return Ok(());
+	}
+
+	let wrapping = Wrapping::new(program);
+
+	write_block(w, tab, program.bodies(), &wrapping, 0)
+}
+
+/// Writes the program as a Python 3 module with a `main` function, which is
+/// also run when the module is run as a script.
+///
+/// Every loop becomes a `while`, and `CPython` refuses to compile more than
+/// 20 statically nested blocks, so programs with loops nested deeper than
+/// that are written but cannot be run.
+///
+/// # Errors
+///
+/// Returns an error if the writer fails.
+pub fn write(writer: &mut dyn Write, program: &Program) -> Result<()> {
+	writeln!(writer, "{PRELUDE}\n\n")?;
+	writeln!(writer, "def main():")?;
+
+	write_main(writer, Tab::new(1), program)?;
+
+	writeln!(writer, "\n")?;
+	writeln!(writer, "if __name__ == \"__main__\":")?;
+	writeln!(writer, "\tmain()")
+}
@@ -0,0 +1,179 @@
+use std::io::{Result, Write};
+
+use crate::mir::data::{Instruction, Program};
+
+use super::layout::{MEMORY_SIZE, MEMORY_START};
+
+// Registers are written as plain temporaries, as QBE puts its input in SSA
+// form on its own.
+struct Function<'a> {
+	w: &'a mut dyn Write,
+	temporaries: usize,
+}
+
+impl<'a> Function<'a> {
+	fn new(w: &'a mut dyn Write) -> Self {
+		Self { w, temporaries: 0 }
+	}
+
+	fn next_temporary(&mut self) -> usize {
+		self.temporaries += 1;
+		self.temporaries
+	}
+
+	// Returns the temporary holding the address of the cell.
+	fn write_address(&mut self, pointer: u32, state: Option<u32>) -> Result<usize> {
+		let offset = self.next_temporary();
+		let address = self.next_temporary();
+
+		match state {
+			Some(state) => writeln!(self.w, "\t%t{offset} =l add %loc_{pointer}, %loc_{state}")?,
+			None => writeln!(self.w, "\t%t{offset} =l add %loc_{pointer}, {MEMORY_START}")?,
+		}
+
+		writeln!(self.w, "\t%t{address} =l add $memory, %t{offset}")?;
+
+		Ok(address)
+	}
+
+	fn write_condition(&mut self, condition: u32) -> Result<usize> {
+		let temporary = self.next_temporary();
+
+		writeln!(self.w, "\t%t{temporary} =l and %loc_{condition}, 255")?;
+
+		Ok(temporary)
+	}
+
+	fn write_select(
+		&mut self,
+		bodies: &[Box<[Instruction]>],
+		condition: u32,
+		code: &[usize],
+	) -> Result<()> {
+		let (last, rest) = code.split_last().unwrap();
+		let condition = self.write_condition(condition)?;
+
+		for (i, code) in rest.iter().enumerate() {
+			let test = self.next_temporary();
+
+			writeln!(self.w, "\t%t{test} =w ceql %t{condition}, {i}")?;
+			writeln!(self.w, "\tjnz %t{test}, @body{code}, @next{code}")?;
+			writeln!(self.w, "@next{code}")?;
+		}
+
+		writeln!(self.w, "\tjmp @body{last}")?;
+
+		for &code in code {
+			writeln!(self.w, "@body{code}")?;
+
+			self.write_block(bodies, code)?;
+
+			writeln!(self.w, "\tjmp @done{last}")?;
+		}
+
+		writeln!(self.w, "@done{last}")
+	}
+
+	fn write_repeat(
+		&mut self,
+		bodies: &[Box<[Instruction]>],
+		code: usize,
+		condition: u32,
+	) -> Result<()> {
+		writeln!(self.w, "@body{code}")?;
+
+		self.write_block(bodies, code)?;
+
+		let condition = self.write_condition(condition)?;
+
+		writeln!(self.w, "\tjnz %t{condition}, @body{code}, @done{code}")?;
+		writeln!(self.w, "@done{code}")
+	}
+
+	#[allow(clippy::cast_possible_wrap)]
+	fn write_insn(&mut self, bodies: &[Box<[Instruction]>], insn: &Instruction) -> Result<()> {
+		match *insn {
+			Instruction::Memory { result } => {
+				writeln!(self.w, "\t%loc_{result} =l copy {MEMORY_START}")
+			}
+			Instruction::IO { result } => writeln!(self.w, "\t%loc_{result} =l copy 0"),
+			Instruction::Integer { result, value } => {
+				writeln!(self.w, "\t%loc_{result} =l copy {}", value as i64)
+			}
+			Instruction::Move { from, to } => {
+				writeln!(self.w, "\t%loc_{to} =l copy %loc_{from}")
+			}
+			Instruction::Add { result, lhs, rhs } => {
+				writeln!(self.w, "\t%loc_{result} =l add %loc_{lhs}, %loc_{rhs}")
+			}
+			Instruction::Sub { result, lhs, rhs } => {
+				writeln!(self.w, "\t%loc_{result} =l sub %loc_{lhs}, %loc_{rhs}")
+			}
+			Instruction::Load {
+				result,
+				pointer,
+				state,
+			} => {
+				let address = self.write_address(pointer, state)?;
+
+				writeln!(self.w, "\t%loc_{result} =l loadub %t{address}")
+			}
+			Instruction::Store {
+				pointer,
+				value,
+				state,
+			} => {
+				let address = self.write_address(pointer, state)?;
+
+				writeln!(self.w, "\tstoreb %loc_{value}, %t{address}")
+			}
+			// Reading past the end of the input gives `EOF`, which is `-1`.
+			Instruction::Ask { result, .. } => {
+				let character = self.next_temporary();
+
+				writeln!(self.w, "\t%t{character} =w call $getchar()")?;
+				writeln!(self.w, "\t%loc_{result} =l extsw %t{character}")
+			}
+			Instruction::Tell { value, .. } => {
+				writeln!(self.w, "\tcall $putchar(w %loc_{value})")
+			}
+			Instruction::Select {
+				condition,
+				ref code,
+			} => self.write_select(bodies, condition, code),
+			Instruction::Repeat { code, condition } => self.write_repeat(bodies, code, condition),
+		}
+	}
+
+	fn write_block(&mut self, bodies: &[Box<[Instruction]>], index: usize) -> Result<()> {
+		bodies[index]
+			.iter()
+			.try_for_each(|insn| self.write_insn(bodies, insn))
+	}
+}
+
+/// Writes the program as QBE intermediate language, with a `main` function
+/// that calls `getchar` and `putchar` from the C library.
+///
+/// # Errors
+///
+/// Returns an error if the writer fails.
+pub fn write(writer: &mut dyn Write, program: &Program) -> Result<()> {
+	writeln!(writer, "data $memory = {{ z {MEMORY_SIZE} }}")?;
+	writeln!(writer)?;
+	writeln!(writer, "export function w $main() {{")?;
+	writeln!(writer, "@start")?;
+
+	for register in 0..program.locals() {
+		writeln!(writer, "\t%loc_{register} =l copy 0")?;
+	}
+
+	let mut function = Function::new(writer);
+
+	if !program.bodies().is_empty() {
+		function.write_block(program.bodies(), 0)?;
+	}
+
+	writeln!(writer, "\tret 0")?;
+	writeln!(writer, "}}")
+}
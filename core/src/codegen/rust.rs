@@ -0,0 +1,183 @@
+use std::io::{Result, Write};
+
+use crate::mir::data::{Instruction, Program};
+
+use super::{
+	layout::{MEMORY_SIZE, MEMORY_START},
+	tab::Tab,
+};
+
+static HELPERS: &str = "use std::io::{ErrorKind, Read, Result, Write};
+
+#[allow(dead_code)]
+fn ask(input: &mut dyn Read) -> Result<u32> {
+	let mut byte = [0];
+
+	match input.read_exact(&mut byte) {
+		Ok(()) => Ok(u32::from(byte[0])),
+		Err(error) if error.kind() == ErrorKind::UnexpectedEof => Ok(u32::MAX),
+		Err(error) => Err(error),
+	}
+}
+
+#[allow(dead_code)]
+fn tell(output: &mut dyn Write, value: u32) -> Result<()> {
+	output.write_all(&[value as u8])
+}";
+
+// Registers that end up unused or are assigned before being read would warn.
+// The lints are allowed on the function only, as an inner attribute would be
+// rejected when the module is included elsewhere.
+static LINTS: &str = "unused_assignments, unused_mut, unused_variables";
+
+fn write_cell(w: &mut dyn Write, pointer: u32, state: Option<u32>) -> Result<()> {
+	match state {
+		Some(state) => write!(
+			w,
+			"memory[loc_{pointer}.wrapping_add(loc_{state}) as usize]"
+		),
+		None => write!(
+			w,
+			"memory[loc_{pointer}.wrapping_add({MEMORY_START}) as usize]"
+		),
+	}
+}
+
+#[allow(clippy::cast_possible_truncation)]
+fn write_insn(
+	w: &mut dyn Write,
+	tab: Tab,
+	bodies: &[Box<[Instruction]>],
+	insn: &Instruction,
+) -> Result<()> {
+	match insn {
+		Instruction::Memory { result } => writeln!(w, "loc_{result} = {MEMORY_START};"),
+		Instruction::IO { result } => writeln!(w, "loc_{result} = 0;"),
+		Instruction::Integer { result, value } => {
+			writeln!(w, "loc_{result} = {};", *value as u32)
+		}
+		Instruction::Move { from, to } => writeln!(w, "loc_{to} = loc_{from};"),
+		Instruction::Add { result, lhs, rhs } => {
+			writeln!(w, "loc_{result} = loc_{lhs}.wrapping_add(loc_{rhs});")
+		}
+		Instruction::Sub { result, lhs, rhs } => {
+			writeln!(w, "loc_{result} = loc_{lhs}.wrapping_sub(loc_{rhs});")
+		}
+		Instruction::Load {
+			result,
+			pointer,
+			state,
+		} => {
+			write!(w, "loc_{result} = u32::from(")?;
+			write_cell(w, *pointer, *state)?;
+			writeln!(w, ");")
+		}
+		Instruction::Store {
+			pointer,
+			value,
+			state,
+		} => {
+			write_cell(w, *pointer, *state)?;
+			writeln!(w, " = loc_{value} as u8;")
+		}
+		Instruction::Ask { result, .. } => writeln!(w, "loc_{result} = ask(&mut input)?;"),
+		Instruction::Tell { value, .. } => writeln!(w, "tell(&mut output, loc_{value})?;"),
+		Instruction::Select { condition, code } => {
+			let mut iter = code.iter();
+			let last = iter.next_back().unwrap();
+
+			writeln!(w, "match loc_{condition} & 0xFF {{")?;
+
+			for (i, code) in iter.enumerate() {
+				writeln!(w, "{tab}\t{i} => {{")?;
+				write_block(w, tab.add().add(), bodies, *code)?;
+				writeln!(w, "{tab}\t}}")?;
+			}
+
+			writeln!(w, "{tab}\t_ => {{")?;
+			write_block(w, tab.add().add(), bodies, *last)?;
+			writeln!(w, "{tab}\t}}")?;
+			writeln!(w, "{tab}}}")
+		}
+		Instruction::Repeat { code, condition } => {
+			writeln!(w, "loop {{")?;
+
+			write_block(w, tab.add(), bodies, *code)?;
+
+			writeln!(w, "{tab}\tif loc_{condition} & 0xFF == 0 {{")?;
+			writeln!(w, "{tab}\t\tbreak;")?;
+			writeln!(w, "{tab}\t}}")?;
+			writeln!(w, "{tab}}}")
+		}
+	}
+}
+
+fn write_block(
+	w: &mut dyn Write,
+	tab: Tab,
+	bodies: &[Box<[Instruction]>],
+	index: usize,
+) -> Result<()> {
+	bodies[index].iter().try_for_each(|insn| {
+		write!(w, "{tab}")?;
+
+		write_insn(w, tab, bodies, insn)
+	})
+}
+
+fn write_entry(w: &mut dyn Write, tab: Tab, program: &Program) -> Result<()> {
+	writeln!(w, "{tab}let mut memory = vec![0_u8; {MEMORY_SIZE}];")?;
+
+	for index in 0..program.locals() {
+		writeln!(w, "{tab}let mut loc_{index}: u32 = 0;")?;
+	}
+
+	if !program.bodies().is_empty() {
+		write_block(w, tab, program.bodies(), 0)?;
+	}
+
+	writeln!(w, "{tab}output.flush()")
+}
+
+/// Writes the program as a self-contained Rust module with a `run` function
+/// that reads from and writes to any stream, with arithmetic wrapping as in C.
+///
+/// # Errors
+///
+/// Returns an error if the writer fails.
+pub fn write(writer: &mut dyn Write, program: &Program) -> Result<()> {
+	writeln!(writer, "{HELPERS}\n")?;
+
+	writeln!(writer, "#[allow({LINTS})]")?;
+	writeln!(
+		writer,
+		"pub fn run(mut input: impl Read, mut output: impl Write) -> Result<()> {{"
+	)?;
+
+	write_entry(writer, Tab::new(1), program)?;
+
+	writeln!(writer, "}}")
+}
+
+/// Writes the program as a block expression that evaluates to a function of
+/// type `fn(&mut dyn Read, &mut dyn Write) -> std::io::Result<()>`.
+///
+/// # Errors
+///
+/// Returns an error if the writer fails.
+pub fn write_expression(writer: &mut dyn Write, program: &Program) -> Result<()> {
+	writeln!(writer, "{{")?;
+	writeln!(writer, "{HELPERS}\n")?;
+
+	writeln!(writer, "#[allow({LINTS})]")?;
+	writeln!(
+		writer,
+		"fn run(mut input: &mut dyn Read, mut output: &mut dyn Write) -> Result<()> {{"
+	)?;
+
+	write_entry(writer, Tab::new(1), program)?;
+
+	writeln!(writer, "}}\n")?;
+	writeln!(writer, "run")?;
+	writeln!(writer, "}}")
+}
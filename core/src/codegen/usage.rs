@@ -0,0 +1,38 @@
+use crate::mir::data::Instruction;
+
+pub(super) fn is_read_in(bodies: &[Box<[Instruction]>], code: usize, register: u32) -> bool {
+	bodies[code].iter().any(|insn| {
+		insn.reads().contains(&register)
+			|| match insn {
+				Instruction::Select { code, .. } => {
+					code.iter().any(|&code| is_read_in(bodies, code, register))
+				}
+				Instruction::Repeat { code, .. } => is_read_in(bodies, *code, register),
+				_ => false,
+			}
+	})
+}
+
+// Whether the body always writes the register before anything reads it, in
+// which case its value on entry to the body does not matter.
+pub(super) fn is_written_first(bodies: &[Box<[Instruction]>], code: usize, register: u32) -> bool {
+	for insn in &bodies[code] {
+		let nested = match insn {
+			Instruction::Select { code, .. } => {
+				code.iter().any(|&code| is_read_in(bodies, code, register))
+			}
+			Instruction::Repeat { code, .. } => is_read_in(bodies, *code, register),
+			_ => false,
+		};
+
+		if nested || insn.reads().contains(&register) {
+			return false;
+		}
+
+		if insn.writes() == Some(register) {
+			return true;
+		}
+	}
+
+	false
+}
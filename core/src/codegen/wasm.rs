@@ -0,0 +1,427 @@
+//! Lowers programs to a WebAssembly module, as text or binary. The module
+//! imports `env.getchar`, which returns `-1` past the end of the input, and
+//! `env.putchar`, which writes the low byte of its argument. It exports its
+//! `memory` and a `main` function that runs the program.
+
+use std::io::{Result, Write};
+
+use crate::mir::data::{Instruction, Program};
+
+use super::layout::MEMORY_START;
+
+const GETCHAR: u32 = 0;
+const PUTCHAR: u32 = 1;
+const MAIN: u32 = 2;
+
+enum Op {
+	Block,
+	Loop,
+	If,
+	Else,
+	End,
+	Br(u32),
+	BrIf(u32),
+	BrTable(Vec<u32>, u32),
+	Call(u32),
+	LocalGet(u32),
+	LocalSet(u32),
+	I32Const(i32),
+	I32Add,
+	I32Sub,
+	I32And,
+	I32Load8U,
+	I32Store8,
+}
+
+fn start() -> u32 {
+	u32::try_from(MEMORY_START).unwrap()
+}
+
+#[derive(Default)]
+struct Lowering {
+	ops: Vec<Op>,
+}
+
+impl Lowering {
+	// The base is added rather than used as the access offset, because only
+	// the addition wraps when the pointer is negative.
+	fn add_address(&mut self, pointer: u32, state: Option<u32>) {
+		self.ops.push(Op::LocalGet(pointer));
+
+		match state {
+			Some(state) => self.ops.push(Op::LocalGet(state)),
+			None => self.ops.push(Op::I32Const(start().try_into().unwrap())),
+		}
+
+		self.ops.push(Op::I32Add);
+	}
+
+	fn add_condition(&mut self, condition: u32) {
+		self.ops.push(Op::LocalGet(condition));
+		self.ops.push(Op::I32Const(0xFF));
+		self.ops.push(Op::I32And);
+	}
+
+	// The first region runs on zero, so with two regions the condition
+	// picks the second as an `if`.
+	fn add_select(&mut self, bodies: &[Box<[Instruction]>], condition: u32, code: &[usize]) {
+		if let [first, second] = *code {
+			self.add_condition(condition);
+			self.ops.push(Op::If);
+			self.add_block(bodies, second);
+			self.ops.push(Op::Else);
+			self.add_block(bodies, first);
+			self.ops.push(Op::End);
+
+			return;
+		}
+
+		let last = u32::try_from(code.len() - 1).unwrap();
+
+		self.ops.push(Op::Block);
+
+		for _ in code {
+			self.ops.push(Op::Block);
+		}
+
+		self.add_condition(condition);
+		self.ops.push(Op::BrTable((0..last).collect(), last));
+
+		for (depth, &code) in (0..=last).rev().zip(code) {
+			self.ops.push(Op::End);
+			self.add_block(bodies, code);
+
+			if depth != 0 {
+				self.ops.push(Op::Br(depth));
+			}
+		}
+
+		self.ops.push(Op::End);
+	}
+
+	fn add_repeat(&mut self, bodies: &[Box<[Instruction]>], code: usize, condition: u32) {
+		self.ops.push(Op::Loop);
+		self.add_block(bodies, code);
+		self.add_condition(condition);
+		self.ops.push(Op::BrIf(0));
+		self.ops.push(Op::End);
+	}
+
+	#[allow(clippy::cast_possible_truncation)]
+	fn add_insn(&mut self, bodies: &[Box<[Instruction]>], insn: &Instruction) {
+		match *insn {
+			Instruction::Memory { result } => {
+				self.ops.push(Op::I32Const(start().try_into().unwrap()));
+				self.ops.push(Op::LocalSet(result));
+			}
+			Instruction::IO { result } => {
+				self.ops.push(Op::I32Const(0));
+				self.ops.push(Op::LocalSet(result));
+			}
+			// Values are 32 bits wide and wrap like they do in C.
+			Instruction::Integer { result, value } => {
+				self.ops.push(Op::I32Const(value as i32));
+				self.ops.push(Op::LocalSet(result));
+			}
+			Instruction::Move { from, to } => {
+				self.ops.push(Op::LocalGet(from));
+				self.ops.push(Op::LocalSet(to));
+			}
+			Instruction::Add { result, lhs, rhs } => {
+				self.ops.push(Op::LocalGet(lhs));
+				self.ops.push(Op::LocalGet(rhs));
+				self.ops.push(Op::I32Add);
+				self.ops.push(Op::LocalSet(result));
+			}
+			Instruction::Sub { result, lhs, rhs } => {
+				self.ops.push(Op::LocalGet(lhs));
+				self.ops.push(Op::LocalGet(rhs));
+				self.ops.push(Op::I32Sub);
+				self.ops.push(Op::LocalSet(result));
+			}
+			Instruction::Load {
+				result,
+				pointer,
+				state,
+			} => {
+				self.add_address(pointer, state);
+				self.ops.push(Op::I32Load8U);
+				self.ops.push(Op::LocalSet(result));
+			}
+			Instruction::Store {
+				pointer,
+				value,
+				state,
+			} => {
+				self.add_address(pointer, state);
+				self.ops.push(Op::LocalGet(value));
+				self.ops.push(Op::I32Store8);
+			}
+			Instruction::Ask { result, .. } => {
+				self.ops.push(Op::Call(GETCHAR));
+				self.ops.push(Op::LocalSet(result));
+			}
+			Instruction::Tell { value, .. } => {
+				self.ops.push(Op::LocalGet(value));
+				self.ops.push(Op::Call(PUTCHAR));
+			}
+			Instruction::Select {
+				condition,
+				ref code,
+			} => self.add_select(bodies, condition, code),
+			Instruction::Repeat { code, condition } => self.add_repeat(bodies, code, condition),
+		}
+	}
+
+	fn add_block(&mut self, bodies: &[Box<[Instruction]>], index: usize) {
+		for insn in &bodies[index] {
+			self.add_insn(bodies, insn);
+		}
+	}
+
+	fn run(program: &Program) -> Vec<Op> {
+		let mut lowering = Self::default();
+
+		if !program.bodies().is_empty() {
+			lowering.add_block(program.bodies(), 0);
+		}
+
+		lowering.ops
+	}
+}
+
+fn write_op_text(w: &mut dyn Write, op: &Op) -> Result<()> {
+	match op {
+		Op::Block => writeln!(w, "block"),
+		Op::Loop => writeln!(w, "loop"),
+		Op::If => writeln!(w, "if"),
+		Op::Else => writeln!(w, "else"),
+		Op::End => writeln!(w, "end"),
+		Op::Br(depth) => writeln!(w, "br {depth}"),
+		Op::BrIf(depth) => writeln!(w, "br_if {depth}"),
+		Op::BrTable(list, default) => {
+			write!(w, "br_table")?;
+
+			for depth in list {
+				write!(w, " {depth}")?;
+			}
+
+			writeln!(w, " {default}")
+		}
+		Op::Call(function) => writeln!(w, "call {function}"),
+		Op::LocalGet(local) => writeln!(w, "local.get {local}"),
+		Op::LocalSet(local) => writeln!(w, "local.set {local}"),
+		Op::I32Const(value) => writeln!(w, "i32.const {value}"),
+		Op::I32Add => writeln!(w, "i32.add"),
+		Op::I32Sub => writeln!(w, "i32.sub"),
+		Op::I32And => writeln!(w, "i32.and"),
+		Op::I32Load8U => writeln!(w, "i32.load8_u"),
+		Op::I32Store8 => writeln!(w, "i32.store8"),
+	}
+}
+
+/// Writes the program as a module in the WebAssembly text format.
+///
+/// # Errors
+///
+/// Returns an error if the writer fails.
+pub fn write_text(writer: &mut dyn Write, program: &Program) -> Result<()> {
+	writeln!(writer, "(module")?;
+	writeln!(writer, "\t(import \"env\" \"getchar\" (func (result i32)))")?;
+	writeln!(writer, "\t(import \"env\" \"putchar\" (func (param i32)))")?;
+	writeln!(writer, "\t(memory (export \"memory\") 1)")?;
+	writeln!(writer, "\t(func (export \"main\")")?;
+
+	if program.locals() != 0 {
+		write!(writer, "\t\t(local")?;
+
+		for _ in 0..program.locals() {
+			write!(writer, " i32")?;
+		}
+
+		writeln!(writer, ")")?;
+	}
+
+	let mut depth = 2;
+
+	for op in Lowering::run(program) {
+		if matches!(op, Op::Else | Op::End) {
+			depth -= 1;
+		}
+
+		(0..depth).try_for_each(|_| write!(writer, "\t"))?;
+		write_op_text(writer, &op)?;
+
+		if matches!(op, Op::Block | Op::Loop | Op::If | Op::Else) {
+			depth += 1;
+		}
+	}
+
+	writeln!(writer, "\t)")?;
+	writeln!(writer, ")")
+}
+
+fn push_unsigned(buffer: &mut Vec<u8>, mut value: u64) {
+	loop {
+		let byte = u8::try_from(value & 0x7F).unwrap();
+
+		value >>= 7;
+
+		if value == 0 {
+			buffer.push(byte);
+
+			break;
+		}
+
+		buffer.push(byte | 0x80);
+	}
+}
+
+fn push_signed(buffer: &mut Vec<u8>, mut value: i64) {
+	loop {
+		let byte = u8::try_from(value & 0x7F).unwrap();
+
+		value >>= 7;
+
+		if (value == 0 && byte & 0x40 == 0) || (value == -1 && byte & 0x40 != 0) {
+			buffer.push(byte);
+
+			break;
+		}
+
+		buffer.push(byte | 0x80);
+	}
+}
+
+fn push_length(buffer: &mut Vec<u8>, length: usize) {
+	push_unsigned(buffer, u64::try_from(length).unwrap());
+}
+
+fn push_name(buffer: &mut Vec<u8>, name: &str) {
+	push_length(buffer, name.len());
+	buffer.extend_from_slice(name.as_bytes());
+}
+
+fn push_op(buffer: &mut Vec<u8>, op: &Op) {
+	match *op {
+		Op::Block => buffer.extend_from_slice(&[0x02, 0x40]),
+		Op::Loop => buffer.extend_from_slice(&[0x03, 0x40]),
+		Op::If => buffer.extend_from_slice(&[0x04, 0x40]),
+		Op::Else => buffer.push(0x05),
+		Op::End => buffer.push(0x0B),
+		Op::Br(depth) => {
+			buffer.push(0x0C);
+			push_unsigned(buffer, depth.into());
+		}
+		Op::BrIf(depth) => {
+			buffer.push(0x0D);
+			push_unsigned(buffer, depth.into());
+		}
+		Op::BrTable(ref list, default) => {
+			buffer.push(0x0E);
+			push_length(buffer, list.len());
+
+			for &depth in list {
+				push_unsigned(buffer, depth.into());
+			}
+
+			push_unsigned(buffer, default.into());
+		}
+		Op::Call(function) => {
+			buffer.push(0x10);
+			push_unsigned(buffer, function.into());
+		}
+		Op::LocalGet(local) => {
+			buffer.push(0x20);
+			push_unsigned(buffer, local.into());
+		}
+		Op::LocalSet(local) => {
+			buffer.push(0x21);
+			push_unsigned(buffer, local.into());
+		}
+		Op::I32Const(value) => {
+			buffer.push(0x41);
+			push_signed(buffer, value.into());
+		}
+		Op::I32Add => buffer.push(0x6A),
+		Op::I32Sub => buffer.push(0x6B),
+		Op::I32And => buffer.push(0x71),
+		Op::I32Load8U => buffer.extend_from_slice(&[0x2D, 0x00, 0x00]),
+		Op::I32Store8 => buffer.extend_from_slice(&[0x3A, 0x00, 0x00]),
+	}
+}
+
+fn write_section(w: &mut dyn Write, id: u8, contents: &[u8]) -> Result<()> {
+	let mut header = vec![id];
+
+	push_length(&mut header, contents.len());
+
+	w.write_all(&header)?;
+	w.write_all(contents)
+}
+
+fn write_code(w: &mut dyn Write, program: &Program) -> Result<()> {
+	let mut body = Vec::new();
+
+	if program.locals() == 0 {
+		body.push(0);
+	} else {
+		body.push(1);
+		push_length(&mut body, program.locals());
+		body.push(0x7F);
+	}
+
+	for op in Lowering::run(program) {
+		push_op(&mut body, &op);
+	}
+
+	body.push(0x0B);
+
+	let mut contents = vec![1];
+
+	push_length(&mut contents, body.len());
+	contents.extend(body);
+
+	write_section(w, 10, &contents)
+}
+
+/// Writes the program as a module in the WebAssembly binary format.
+///
+/// # Errors
+///
+/// Returns an error if the writer fails.
+pub fn write_binary(writer: &mut dyn Write, program: &Program) -> Result<()> {
+	writer.write_all(b"\0asm")?;
+	writer.write_all(&1_u32.to_le_bytes())?;
+
+	// Types of `getchar`, `putchar` and `main`.
+	write_section(
+		writer,
+		1,
+		&[3, 0x60, 0, 1, 0x7F, 0x60, 1, 0x7F, 0, 0x60, 0, 0],
+	)?;
+
+	let mut imports = vec![2];
+
+	for (name, kind) in [("getchar", GETCHAR), ("putchar", PUTCHAR)] {
+		push_name(&mut imports, "env");
+		push_name(&mut imports, name);
+		imports.push(0x00);
+		push_unsigned(&mut imports, kind.into());
+	}
+
+	write_section(writer, 2, &imports)?;
+	write_section(writer, 3, &[1, 2])?;
+	write_section(writer, 5, &[1, 0x00, 1])?;
+
+	let mut exports = vec![2];
+
+	push_name(&mut exports, "memory");
+	exports.extend_from_slice(&[0x02, 0]);
+	push_name(&mut exports, "main");
+	exports.push(0x00);
+	push_unsigned(&mut exports, MAIN.into());
+
+	write_section(writer, 7, &exports)?;
+	write_code(writer, program)
+}
@@ -0,0 +1,199 @@
+use crate::mir::data::{Instruction, Program};
+
+fn index_of(register: u32) -> usize {
+	usize::try_from(register).unwrap()
+}
+
+fn join(into: &mut [bool], from: &[bool]) {
+	into.iter_mut().zip(from).for_each(|(a, b)| *a |= b);
+}
+
+#[derive(Clone, PartialEq, Eq)]
+struct State {
+	// Whether the register holds a cell value rather than a pointer.
+	cell: Vec<bool>,
+	// Whether the register may hold a value outside of a byte.
+	unwrapped: Vec<bool>,
+}
+
+impl State {
+	fn new(locals: usize) -> Self {
+		Self {
+			cell: vec![false; locals],
+			unwrapped: vec![false; locals],
+		}
+	}
+
+	fn join(&mut self, other: &Self) {
+		join(&mut self.cell, &other.cell);
+		join(&mut self.unwrapped, &other.unwrapped);
+	}
+
+	fn set(&mut self, register: u32, cell: bool, unwrapped: bool) {
+		self.cell[index_of(register)] = cell;
+		self.unwrapped[index_of(register)] = unwrapped;
+	}
+
+	fn is_unwrapped(&self, register: u32) -> bool {
+		self.unwrapped[index_of(register)]
+	}
+}
+
+/// Decides where values are wrapped to a byte for targets whose numbers do
+/// not wrap on their own. Cell arithmetic is either wrapped right away, or
+/// left alone and wrapped only where a byte is observed, which is when it is
+/// stored, written out or tested. Whichever needs fewer masks is chosen, and
+/// observations of values that may still be out of range are always masked.
+pub struct Wrapping {
+	masks: Vec<Vec<bool>>,
+	on_arithmetic: bool,
+}
+
+impl Wrapping {
+	fn empty(program: &Program, on_arithmetic: bool) -> Self {
+		let masks = program
+			.bodies()
+			.iter()
+			.map(|body| vec![false; body.len()])
+			.collect();
+
+		Self {
+			masks,
+			on_arithmetic,
+		}
+	}
+
+	fn observe(&mut self, state: &State, code: usize, position: usize, register: u32) {
+		if state.is_unwrapped(register) {
+			self.masks[code][position] = true;
+		}
+	}
+
+	fn visit_insn(
+		&mut self,
+		bodies: &[Box<[Instruction]>],
+		state: &mut State,
+		code: usize,
+		position: usize,
+	) {
+		match bodies[code][position] {
+			Instruction::Memory { result } | Instruction::IO { result } => {
+				state.set(result, false, false);
+			}
+			Instruction::Integer { result, value } => state.set(result, false, value > 0xFF),
+			Instruction::Move { from, to } => {
+				let cell = state.cell[index_of(from)];
+
+				state.set(to, cell, state.is_unwrapped(from));
+			}
+			Instruction::Add { result, lhs, rhs } | Instruction::Sub { result, lhs, rhs } => {
+				let cell = state.cell[index_of(lhs)] || state.cell[index_of(rhs)];
+				let masked = self.on_arithmetic && cell;
+
+				self.masks[code][position] = masked;
+
+				state.set(result, cell, !masked);
+			}
+			Instruction::Load { result, .. } => state.set(result, true, false),
+			// Reading past the end of the input gives `-1`.
+			Instruction::Ask { result, .. } => {
+				self.masks[code][position] = self.on_arithmetic;
+
+				state.set(result, true, !self.on_arithmetic);
+			}
+			Instruction::Store { value, .. } | Instruction::Tell { value, .. } => {
+				self.observe(state, code, position, value);
+			}
+			Instruction::Select {
+				condition,
+				code: ref list,
+			} => {
+				self.observe(state, code, position, condition);
+
+				let mut joined: Option<State> = None;
+
+				for &inner in list.iter() {
+					let mut branch = state.clone();
+
+					self.visit_body(bodies, &mut branch, inner);
+
+					match &mut joined {
+						Some(joined) => joined.join(&branch),
+						None => joined = Some(branch),
+					}
+				}
+
+				if let Some(joined) = joined {
+					*state = joined;
+				}
+			}
+			Instruction::Repeat {
+				code: inner,
+				condition,
+			} => {
+				let mut entry = state.clone();
+
+				loop {
+					let mut exit = entry.clone();
+
+					self.visit_body(bodies, &mut exit, inner);
+					self.observe(&exit, code, position, condition);
+
+					let mut next = entry.clone();
+
+					next.join(&exit);
+
+					if next == entry {
+						*state = exit;
+
+						break;
+					}
+
+					entry = next;
+				}
+			}
+		}
+	}
+
+	fn visit_body(&mut self, bodies: &[Box<[Instruction]>], state: &mut State, code: usize) {
+		for position in 0..bodies[code].len() {
+			self.visit_insn(bodies, state, code, position);
+		}
+	}
+
+	fn count(&self) -> usize {
+		self.masks.iter().flatten().filter(|&&mask| mask).count()
+	}
+
+	fn with_strategy(program: &Program, on_arithmetic: bool) -> Self {
+		let mut wrapping = Self::empty(program, on_arithmetic);
+
+		if !program.bodies().is_empty() {
+			let mut state = State::new(program.locals());
+
+			wrapping.visit_body(program.bodies(), &mut state, 0);
+		}
+
+		wrapping
+	}
+
+	#[must_use]
+	pub fn new(program: &Program) -> Self {
+		let on_use = Self::with_strategy(program, false);
+		let on_arithmetic = Self::with_strategy(program, true);
+
+		if on_arithmetic.count() < on_use.count() {
+			on_arithmetic
+		} else {
+			on_use
+		}
+	}
+
+	/// Returns whether the instruction at the position must be wrapped. For
+	/// instructions with a result that is the result itself, for the rest it
+	/// is the value or condition they observe.
+	#[must_use]
+	pub fn is_masked(&self, code: usize, position: usize) -> bool {
+		self.masks[code][position]
+	}
+}
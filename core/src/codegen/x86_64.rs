@@ -0,0 +1,230 @@
+use std::{
+	fmt::{Display, Formatter},
+	io::{Result, Write},
+};
+
+use crate::mir::data::{Instruction, Program};
+
+use super::layout::{MEMORY_SIZE, MEMORY_START};
+
+static BUFFER_SIZE: usize = 4096;
+
+// Registers that survive `syscall`, leaving `rax`, `rcx`, `rdx`, `rsi`, `rdi`
+// and `r11` free for scratch and system calls. The tape base lives in `r15`.
+static REGISTERS: [&str; 8] = ["rbx", "rbp", "r12", "r13", "r14", "r8", "r9", "r10"];
+
+/// Where a register of the program lives, either a machine register or a
+/// stack slot once those run out.
+struct Location(u32);
+
+impl Display for Location {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		let index = usize::try_from(self.0).unwrap();
+
+		match REGISTERS.get(index) {
+			Some(name) => write!(f, "%{name}"),
+			None => write!(f, "{}(%rsp)", (index - REGISTERS.len()) * 8),
+		}
+	}
+}
+
+fn write_helpers(w: &mut dyn Write) -> Result<()> {
+	writeln!(
+		w,
+		"tell:
+	movq out_len(%rip), %rcx
+	leaq out_buf(%rip), %rdx
+	movb %al, (%rdx,%rcx)
+	incq %rcx
+	movq %rcx, out_len(%rip)
+	cmpq ${BUFFER_SIZE}, %rcx
+	je flush
+	ret
+flush:
+	movl $1, %eax
+	movl $1, %edi
+	leaq out_buf(%rip), %rsi
+	movq out_len(%rip), %rdx
+	syscall
+	movq $0, out_len(%rip)
+	ret
+ask:
+	call flush
+	xorl %eax, %eax
+	xorl %edi, %edi
+	leaq in_byte(%rip), %rsi
+	movl $1, %edx
+	syscall
+	testq %rax, %rax
+	jle 1f
+	movzbl in_byte(%rip), %eax
+	ret
+1:
+	movq $-1, %rax
+	ret"
+	)
+}
+
+// Leaves the address of the cell in `rax`, with the displacement to use.
+fn write_address(w: &mut dyn Write, pointer: u32, state: Option<u32>) -> Result<usize> {
+	writeln!(w, "\tmovq {}, %rax", Location(pointer))?;
+
+	match state {
+		Some(state) => {
+			writeln!(w, "\taddq {}, %rax", Location(state))?;
+
+			Ok(0)
+		}
+		None => Ok(MEMORY_START),
+	}
+}
+
+#[allow(clippy::cast_possible_wrap)]
+fn write_integer(w: &mut dyn Write, result: u32, value: u64) -> Result<()> {
+	let value = value as i64;
+
+	if i32::try_from(value).is_ok() {
+		writeln!(w, "\tmovq ${value}, {}", Location(result))
+	} else {
+		writeln!(w, "\tmovabsq ${value}, %rax")?;
+		writeln!(w, "\tmovq %rax, {}", Location(result))
+	}
+}
+
+fn write_arithmetic(w: &mut dyn Write, name: &str, result: u32, lhs: u32, rhs: u32) -> Result<()> {
+	writeln!(w, "\tmovq {}, %rax", Location(lhs))?;
+	writeln!(w, "\t{name} {}, %rax", Location(rhs))?;
+	writeln!(w, "\tmovq %rax, {}", Location(result))
+}
+
+fn write_select(
+	w: &mut dyn Write,
+	bodies: &[Box<[Instruction]>],
+	condition: u32,
+	code: &[usize],
+) -> Result<()> {
+	let (last, rest) = code.split_last().unwrap();
+
+	writeln!(w, "\tmovq {}, %rax", Location(condition))?;
+
+	for (i, code) in rest.iter().enumerate() {
+		writeln!(w, "\tcmpb ${i}, %al")?;
+		writeln!(w, "\tje .Lbody{code}")?;
+	}
+
+	writeln!(w, "\tjmp .Lbody{last}")?;
+
+	for &code in rest {
+		writeln!(w, ".Lbody{code}:")?;
+		write_block(w, bodies, code)?;
+		writeln!(w, "\tjmp .Ldone{last}")?;
+	}
+
+	writeln!(w, ".Lbody{last}:")?;
+	write_block(w, bodies, *last)?;
+	writeln!(w, ".Ldone{last}:")
+}
+
+fn write_repeat(
+	w: &mut dyn Write,
+	bodies: &[Box<[Instruction]>],
+	code: usize,
+	condition: u32,
+) -> Result<()> {
+	writeln!(w, ".Lbody{code}:")?;
+	write_block(w, bodies, code)?;
+	writeln!(w, "\tmovq {}, %rax", Location(condition))?;
+	writeln!(w, "\ttestb %al, %al")?;
+	writeln!(w, "\tjnz .Lbody{code}")
+}
+
+fn write_insn(w: &mut dyn Write, bodies: &[Box<[Instruction]>], insn: &Instruction) -> Result<()> {
+	match *insn {
+		Instruction::Memory { result } => {
+			writeln!(w, "\tmovq ${MEMORY_START}, {}", Location(result))
+		}
+		Instruction::IO { result } => writeln!(w, "\tmovq $0, {}", Location(result)),
+		Instruction::Integer { result, value } => write_integer(w, result, value),
+		Instruction::Move { from, to } => {
+			writeln!(w, "\tmovq {}, %rax", Location(from))?;
+			writeln!(w, "\tmovq %rax, {}", Location(to))
+		}
+		Instruction::Add { result, lhs, rhs } => write_arithmetic(w, "addq", result, lhs, rhs),
+		Instruction::Sub { result, lhs, rhs } => write_arithmetic(w, "subq", result, lhs, rhs),
+		Instruction::Load {
+			result,
+			pointer,
+			state,
+		} => {
+			let offset = write_address(w, pointer, state)?;
+
+			writeln!(w, "\tmovzbl {offset}(%r15,%rax), %eax")?;
+			writeln!(w, "\tmovq %rax, {}", Location(result))
+		}
+		Instruction::Store {
+			pointer,
+			value,
+			state,
+		} => {
+			let offset = write_address(w, pointer, state)?;
+
+			writeln!(w, "\tmovq {}, %rcx", Location(value))?;
+			writeln!(w, "\tmovb %cl, {offset}(%r15,%rax)")
+		}
+		Instruction::Ask { result, .. } => {
+			writeln!(w, "\tcall ask")?;
+			writeln!(w, "\tmovq %rax, {}", Location(result))
+		}
+		Instruction::Tell { value, .. } => {
+			writeln!(w, "\tmovq {}, %rax", Location(value))?;
+			writeln!(w, "\tcall tell")
+		}
+		Instruction::Select {
+			condition,
+			ref code,
+		} => write_select(w, bodies, condition, code),
+		Instruction::Repeat { code, condition } => write_repeat(w, bodies, code, condition),
+	}
+}
+
+fn write_block(w: &mut dyn Write, bodies: &[Box<[Instruction]>], index: usize) -> Result<()> {
+	bodies[index]
+		.iter()
+		.try_for_each(|insn| write_insn(w, bodies, insn))
+}
+
+/// Writes the program as x86-64 assembly for the GNU assembler, to be linked
+/// without libc into a static Linux executable.
+///
+/// # Errors
+///
+/// Returns an error if the writer fails.
+pub fn write(writer: &mut dyn Write, program: &Program) -> Result<()> {
+	let slots = program.locals().saturating_sub(REGISTERS.len());
+
+	writeln!(writer, "\t.text")?;
+	writeln!(writer, "\t.globl _start")?;
+	writeln!(writer, "_start:")?;
+	writeln!(writer, "\tleaq memory(%rip), %r15")?;
+
+	if slots != 0 {
+		writeln!(writer, "\tsubq ${}, %rsp", slots * 8)?;
+	}
+
+	if !program.bodies().is_empty() {
+		write_block(writer, program.bodies(), 0)?;
+	}
+
+	writeln!(writer, "\tcall flush")?;
+	writeln!(writer, "\tmovl $60, %eax")?;
+	writeln!(writer, "\txorl %edi, %edi")?;
+	writeln!(writer, "\tsyscall")?;
+
+	write_helpers(writer)?;
+
+	writeln!(writer, "\t.bss")?;
+	writeln!(writer, "\t.lcomm memory, {MEMORY_SIZE}")?;
+	writeln!(writer, "\t.lcomm out_buf, {BUFFER_SIZE}")?;
+	writeln!(writer, "\t.lcomm out_len, 8")?;
+	writeln!(writer, "\t.lcomm in_byte, 1")
+}
@@ -1,4 +1,7 @@
-use std::io::{Result, Write};
+use std::{
+	collections::HashMap,
+	io::{Result, Write},
+};
 
 use regioned::{
 	data_flow::{
@@ -8,6 +11,30 @@ use regioned::{
 	dot::Description,
 };
 
+/// What a single output port of a node carries.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Kind {
+	Value,
+	Memory,
+	IO,
+}
+
+impl Kind {
+	#[must_use]
+	pub const fn is_state(self) -> bool {
+		!matches!(self, Self::Value)
+	}
+
+	#[must_use]
+	pub const fn name(self) -> &'static str {
+		match self {
+			Self::Value => "value",
+			Self::Memory => "state",
+			Self::IO => "io",
+		}
+	}
+}
+
 pub enum Simple {
 	NoOp,
 
@@ -50,7 +77,8 @@ pub enum Simple {
 }
 
 impl Simple {
-	const fn name(&self) -> &'static str {
+	#[must_use]
+	pub const fn name(&self) -> &'static str {
 		match self {
 			Self::NoOp => "NoOp",
 			Self::Merge { .. } => "Merge",
@@ -65,6 +93,33 @@ impl Simple {
 			Self::Tell { .. } => "Tell",
 		}
 	}
+
+	/// Returns the kinds of the outputs of this node, in port order.
+	#[must_use]
+	pub const fn results(&self) -> &'static [Kind] {
+		match self {
+			Self::NoOp => &[],
+			Self::Merge { .. } | Self::Memory | Self::Store { .. } => &[Kind::Memory],
+			Self::IO | Self::Tell { .. } => &[Kind::IO],
+			Self::Integer { .. } | Self::Add { .. } | Self::Sub { .. } => &[Kind::Value],
+			Self::Load { .. } => &[Kind::Memory, Kind::Value],
+			Self::Ask { .. } => &[Kind::IO, Kind::Value],
+		}
+	}
+
+	/// Returns the kinds expected by the inputs of this node, in parameter order.
+	#[must_use]
+	pub fn arguments(&self) -> Vec<Kind> {
+		match self {
+			Self::NoOp | Self::Memory | Self::IO | Self::Integer { .. } => Vec::new(),
+			Self::Merge { states } => vec![Kind::Memory; states.len()],
+			Self::Add { .. } | Self::Sub { .. } => vec![Kind::Value; 2],
+			Self::Load { .. } => vec![Kind::Memory, Kind::Value],
+			Self::Store { .. } => vec![Kind::Memory, Kind::Value, Kind::Value],
+			Self::Ask { .. } => vec![Kind::IO],
+			Self::Tell { .. } => vec![Kind::IO, Kind::Value],
+		}
+	}
 }
 
 impl AsParametersMut for Simple {
@@ -120,7 +175,19 @@ impl ParametersMut for Simple {
 
 impl Description for Simple {
 	fn write_content(&self, writer: &mut dyn Write) -> Result<()> {
-		write!(writer, "{}", self.name())
+		write!(writer, "{}", self.name())?;
+
+		let ports: &[&str] = match self {
+			Self::Integer { value } => return write!(writer, " {value}"),
+			Self::NoOp | Self::Merge { .. } | Self::Memory | Self::IO => return Ok(()),
+			Self::Add { .. } | Self::Sub { .. } => &["lhs", "rhs"],
+			Self::Load { .. } => &["state", "pointer"],
+			Self::Store { .. } => &["state", "pointer", "value"],
+			Self::Ask { .. } => &["io"],
+			Self::Tell { .. } => &["io", "value"],
+		};
+
+		write!(writer, "({})", ports.join(", "))
 	}
 }
 
@@ -134,6 +201,20 @@ pub type Node = regioned::data_flow::node::Node<Simple>;
 
 pub type Nodes = regioned::data_flow::nodes::Nodes<Simple>;
 
+/// Maps the start and end markers of every region to their compound.
+pub fn find_parents(nodes: &Nodes, parents: &mut HashMap<Id, Id>) {
+	parents.clear();
+
+	for (id, node) in nodes.iter() {
+		if let Node::Compound(compound) = node {
+			for region in compound.regions() {
+				parents.insert(region.start(), id);
+				parents.insert(region.end(), id);
+			}
+		}
+	}
+}
+
 pub trait Builder {
 	fn add_integer(&mut self, value: u64) -> Link;
 
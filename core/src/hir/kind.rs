@@ -0,0 +1,97 @@
+use std::collections::HashMap;
+
+use regioned::{
+	data_flow::{
+		link::{Id, Link},
+		node::{Compound, Marker, Parameters},
+	},
+	visit::reverse_topological::ReverseTopological,
+};
+
+use super::data::{find_parents, Kind, Node, Nodes};
+
+/// Infers the kind of every output port reachable from the roots. Ports of
+/// markers and compounds take the kind of whatever flows into them.
+#[derive(Default)]
+pub struct Kinds {
+	parents: HashMap<Id, Id>,
+	results: Vec<Vec<Kind>>,
+}
+
+impl Kinds {
+	#[must_use]
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	fn set(&mut self, id: Id, kinds: Vec<Kind>) {
+		if id >= self.results.len() {
+			self.results.resize_with(id + 1, Vec::new);
+		}
+
+		self.results[id] = kinds;
+	}
+
+	fn passthrough(&self, links: &[Link]) -> Vec<Kind> {
+		links.iter().map(|&link| self.get(link)).collect()
+	}
+
+	fn marker_results(&self, nodes: &Nodes, id: Id) -> Vec<Kind> {
+		match &nodes[self.parents[&id]] {
+			Node::Compound(Compound::Gamma { parameters, .. }) => parameters
+				.split_last()
+				.map_or_else(Vec::new, |(_, rest)| self.passthrough(rest)),
+			Node::Compound(Compound::Theta { parameters, .. }) => self.passthrough(parameters),
+			_ => unreachable!(),
+		}
+	}
+
+	fn compound_results(&self, nodes: &Nodes, compound: &Compound) -> Vec<Kind> {
+		match compound {
+			Compound::Gamma { regions, .. } => regions.first().map_or_else(Vec::new, |region| {
+				let end: Vec<_> = nodes[region.end()].parameters().copied().collect();
+
+				self.passthrough(&end)
+			}),
+			Compound::Theta { region, .. } => {
+				let end: Vec<_> = nodes[region.end()].parameters().copied().collect();
+
+				end.split_last()
+					.map_or_else(Vec::new, |(_, rest)| self.passthrough(rest))
+			}
+			Compound::Lambda { .. } | Compound::Phi { .. } => unreachable!(),
+		}
+	}
+
+	pub fn run<I>(&mut self, nodes: &Nodes, roots: I, topological: &mut ReverseTopological)
+	where
+		I: IntoIterator<Item = Id>,
+	{
+		find_parents(nodes, &mut self.parents);
+		self.results.iter_mut().for_each(Vec::clear);
+
+		for id in topological.iter(nodes, roots) {
+			let kinds = match &nodes[id] {
+				Node::Simple(simple) => simple.results().to_vec(),
+				Node::Marker(Marker::Start) => self.marker_results(nodes, id),
+				Node::Marker(Marker::End { .. }) => Vec::new(),
+				Node::Compound(compound) => self.compound_results(nodes, compound),
+			};
+
+			self.set(id, kinds);
+		}
+	}
+
+	/// Returns the kind of the port, defaulting to `Kind::Value` for ports
+	/// that were not reached.
+	#[must_use]
+	pub fn get(&self, link: Link) -> Kind {
+		let index = usize::from(link.port);
+
+		self.results
+			.get(link.node)
+			.and_then(|list| list.get(index))
+			.copied()
+			.unwrap_or(Kind::Value)
+	}
+}
@@ -0,0 +1,7 @@
+pub mod data;
+pub mod isle;
+pub mod kind;
+pub mod optimizer;
+pub mod parser;
+pub mod text;
+pub mod verify;
@@ -0,0 +1,187 @@
+use regioned::{
+	data_flow::link::Id,
+	transform::{
+		relax_dependencies::RelaxDependencies,
+		retain_only,
+		revise::{self, redo_ports, redo_ports_in_place},
+	},
+	visit::{reverse_topological::ReverseTopological, successors::Successors},
+};
+
+use super::{
+	data::{Node, Nodes, Simple},
+	isle::{self, Elided},
+	parser::ParseData,
+};
+
+fn run_fold_identity(successors: &Successors) -> impl FnMut(&mut Nodes, Id) -> Option<Node> + '_ {
+	revise::single(
+		|nodes, id| isle::identity(nodes, id.into()),
+		|nodes, id, value| {
+			redo_ports(nodes, successors, id, |port| (port == 0).then_some(value));
+
+			Simple::NoOp.into()
+		},
+	)
+}
+
+fn run_fold_expressions() -> impl FnMut(&mut Nodes, Id) -> Option<Node> {
+	revise::single(
+		|nodes, id| isle::fold(nodes, id.into()),
+		|_, _, math| Simple::from(math).into(),
+	)
+}
+
+fn run_load_store_elision(
+	successors: &Successors,
+) -> impl FnMut(&mut Nodes, Id) -> Option<Node> + '_ {
+	revise::single(
+		|nodes, id| isle::elide(nodes, id.into()),
+		|nodes, id, elided| {
+			let result = match elided {
+				Elided::Merge { state } => {
+					redo_ports_in_place(nodes, successors, id, state.node);
+
+					Simple::NoOp
+				}
+				Elided::Load { store, value } => {
+					redo_ports(nodes, successors, id, |port| match port {
+						0 => Some(store),
+						1 => Some(value),
+						_ => None,
+					});
+
+					Simple::NoOp
+				}
+				Elided::Store {
+					store,
+					pointer,
+					value,
+				} => Simple::Store {
+					state: store,
+					pointer,
+					value,
+				},
+			};
+
+			result.into()
+		},
+	)
+}
+
+/// Rewrites the graph until no more rewrites apply. Each iteration visits
+/// every node once, folding constants, eliding loads and stores, and only
+/// when nothing else applied, relaxing the dependencies of compounds.
+#[allow(clippy::struct_excessive_bools)]
+#[derive(Default)]
+pub struct Optimizer {
+	constant_fold: bool,
+	load_store_elide: bool,
+	relax_dependencies: bool,
+
+	relax: RelaxDependencies,
+	successors: Successors,
+	topological: ReverseTopological,
+	list: Vec<Id>,
+}
+
+impl Optimizer {
+	#[must_use]
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Returns an optimizer with every rewrite enabled.
+	#[must_use]
+	pub fn with_all() -> Self {
+		Self::new()
+			.with_constant_fold(true)
+			.with_load_store_elide(true)
+			.with_relax_dependencies(true)
+	}
+
+	#[must_use]
+	pub const fn with_constant_fold(mut self, constant_fold: bool) -> Self {
+		self.constant_fold = constant_fold;
+		self
+	}
+
+	#[must_use]
+	pub const fn with_load_store_elide(mut self, load_store_elide: bool) -> Self {
+		self.load_store_elide = load_store_elide;
+		self
+	}
+
+	#[must_use]
+	pub const fn with_relax_dependencies(mut self, relax_dependencies: bool) -> Self {
+		self.relax_dependencies = relax_dependencies;
+		self
+	}
+
+	fn run_node(&mut self, nodes: &mut Nodes, id: Id) -> usize {
+		let mut applied = 0;
+
+		if self.constant_fold {
+			if run_fold_identity(&self.successors)(nodes, id).is_some() {
+				applied += 1;
+			}
+
+			if run_fold_expressions()(nodes, id).is_some() {
+				applied += 1;
+			}
+		}
+
+		if self.load_store_elide && run_load_store_elision(&self.successors)(nodes, id).is_some() {
+			applied += 1;
+		}
+
+		if self.relax_dependencies
+			&& applied == 0
+			&& self
+				.relax
+				.run(nodes, id, &self.successors)
+				.unwrap_or_default()
+				!= 0
+		{
+			applied += 1;
+		}
+
+		applied
+	}
+
+	/// Runs a single iteration over the graph, returning how many rewrites
+	/// were applied.
+	pub fn iterate(&mut self, data: &mut ParseData) -> usize {
+		let roots = data.roots();
+		let mut list = std::mem::take(&mut self.list);
+
+		list.clear();
+		list.extend(self.topological.iter(data.nodes(), roots));
+
+		self.successors
+			.run(data.nodes(), roots, &mut self.topological);
+
+		let applied = list
+			.iter()
+			.fold(0, |acc, &id| acc + self.run_node(data.nodes_mut(), id));
+
+		self.list = list;
+
+		applied
+	}
+
+	/// Removes the nodes that are no longer reachable from the roots.
+	pub fn retain(&mut self, data: &mut ParseData) {
+		let roots = data.roots();
+
+		retain_only::run(data.nodes_mut(), roots, &mut self.topological);
+	}
+
+	/// Iterates until no more rewrites apply and then removes the nodes that
+	/// are no longer reachable.
+	pub fn run(&mut self, data: &mut ParseData) {
+		while self.iterate(data) != 0 {}
+
+		self.retain(data);
+	}
+}
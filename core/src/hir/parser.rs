@@ -13,6 +13,11 @@ pub struct ParseData {
 }
 
 impl ParseData {
+	#[must_use]
+	pub const fn new(nodes: Nodes, io: Id) -> Self {
+		Self { nodes, io }
+	}
+
 	#[must_use]
 	pub const fn nodes(&self) -> &Nodes {
 		&self.nodes
@@ -0,0 +1,531 @@
+//! A readable text format for graphs, mostly useful for writing small graphs
+//! by hand and for attaching reduced graphs to bug reports.
+//!
+//! ```text
+//! %0 = IO
+//! %1 = Memory
+//! %2 = Integer 0
+//! %3 = Load %1, %2
+//! %4 = Theta %0, %3, %2 {
+//!     %5 = Start
+//!     %6 = Load %5:1, %5:2
+//!     End %5, %6, %5:2, %6:1
+//! }
+//! return %4
+//! ```
+//!
+//! Links are written as `%N:P`, where the port `P` may be omitted if it is 0.
+//! Nodes are numbered arbitrarily, but must be defined before they are used.
+
+use std::{
+	collections::HashMap,
+	io::{self, Write},
+};
+
+use regioned::{
+	data_flow::{
+		link::{Id, Link, Region},
+		node::{AsParametersMut, Compound, Marker, Parameters},
+	},
+	visit::reverse_topological::ReverseTopological,
+};
+
+use super::{
+	data::{find_parents, Node, Nodes, Simple},
+	parser::ParseData,
+};
+
+fn write_indent(w: &mut dyn Write, depth: usize) -> io::Result<()> {
+	(0..depth).try_for_each(|_| write!(w, "\t"))
+}
+
+fn write_link(w: &mut dyn Write, link: Link) -> io::Result<()> {
+	if link.port == 0 {
+		write!(w, "%{}", link.node)
+	} else {
+		write!(w, "%{}:{}", link.node, link.port)
+	}
+}
+
+fn write_links<'a, I>(w: &mut dyn Write, links: I) -> io::Result<()>
+where
+	I: IntoIterator<Item = &'a Link>,
+{
+	for (index, link) in links.into_iter().enumerate() {
+		if index == 0 {
+			write!(w, " ")?;
+		} else {
+			write!(w, ", ")?;
+		}
+
+		write_link(w, *link)?;
+	}
+
+	Ok(())
+}
+
+#[derive(Default)]
+pub struct Printer {
+	parents: HashMap<Id, Id>,
+	depth: usize,
+}
+
+impl Printer {
+	#[must_use]
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	fn write_simple(&self, w: &mut dyn Write, simple: &Simple, id: Id) -> io::Result<()> {
+		write_indent(w, self.depth)?;
+		write!(w, "%{id} = {}", simple.name())?;
+
+		if let Simple::Integer { value } = simple {
+			write!(w, " {value}")?;
+		}
+
+		write_links(w, simple.parameters())?;
+		writeln!(w)
+	}
+
+	fn write_compound_start(
+		&mut self,
+		w: &mut dyn Write,
+		name: &str,
+		parameters: &[Link],
+		parent: Id,
+	) -> io::Result<()> {
+		write_indent(w, self.depth)?;
+		write!(w, "%{parent} = {name}")?;
+		write_links(w, parameters)?;
+		writeln!(w, " {{")?;
+
+		self.depth += 1;
+
+		Ok(())
+	}
+
+	fn write_start(&mut self, w: &mut dyn Write, nodes: &Nodes, id: Id) -> io::Result<()> {
+		let parent = self.parents[&id];
+
+		match &nodes[parent] {
+			Node::Compound(Compound::Gamma {
+				parameters,
+				regions,
+			}) => {
+				if regions[0].start() == id {
+					self.write_compound_start(w, "Gamma", parameters, parent)?;
+				} else {
+					write_indent(w, self.depth - 1)?;
+					writeln!(w, "}} {{")?;
+				}
+			}
+			Node::Compound(Compound::Theta { parameters, .. }) => {
+				self.write_compound_start(w, "Theta", parameters, parent)?;
+			}
+			_ => unreachable!(),
+		}
+
+		write_indent(w, self.depth)?;
+		writeln!(w, "%{id} = Start")
+	}
+
+	fn write_end(
+		&mut self,
+		w: &mut dyn Write,
+		nodes: &Nodes,
+		id: Id,
+		parameters: &[Link],
+	) -> io::Result<()> {
+		write_indent(w, self.depth)?;
+		write!(w, "End")?;
+		write_links(w, parameters)?;
+		writeln!(w)?;
+
+		let is_last = match &nodes[self.parents[&id]] {
+			Node::Compound(Compound::Gamma { regions, .. }) => regions.last().unwrap().end() == id,
+			Node::Compound(Compound::Theta { .. }) => true,
+			_ => unreachable!(),
+		};
+
+		if is_last {
+			self.depth -= 1;
+
+			write_indent(w, self.depth)?;
+			writeln!(w, "}}")?;
+		}
+
+		Ok(())
+	}
+
+	/// # Errors
+	///
+	/// Returns an error if the writer fails.
+	pub fn write<I>(
+		&mut self,
+		w: &mut dyn Write,
+		nodes: &Nodes,
+		roots: I,
+		topological: &mut ReverseTopological,
+	) -> io::Result<()>
+	where
+		I: IntoIterator<Item = Id> + Clone,
+	{
+		find_parents(nodes, &mut self.parents);
+
+		self.depth = 0;
+
+		for id in topological.iter(nodes, roots.clone()) {
+			match &nodes[id] {
+				Node::Simple(simple) => self.write_simple(w, simple, id)?,
+				Node::Marker(Marker::Start) => self.write_start(w, nodes, id)?,
+				Node::Marker(Marker::End { parameters }) => {
+					self.write_end(w, nodes, id, parameters)?;
+				}
+				Node::Compound(_) => {}
+			}
+		}
+
+		roots
+			.into_iter()
+			.try_for_each(|root| writeln!(w, "return %{root}"))
+	}
+}
+
+#[derive(Debug)]
+pub enum ReadError {
+	UnexpectedToken { line: usize },
+	UnknownName { line: usize },
+	UnbalancedBraces { line: usize },
+	MissingReturn,
+}
+
+enum Frame {
+	Gamma {
+		name: String,
+		parameters: Vec<Link>,
+		regions: Vec<Region>,
+	},
+	Theta {
+		region: Region,
+	},
+}
+
+impl Frame {
+	fn region(&self) -> Region {
+		match self {
+			Self::Gamma { regions, .. } => *regions.last().unwrap(),
+			Self::Theta { region } => *region,
+		}
+	}
+}
+
+#[derive(Default)]
+pub struct Reader {
+	nodes: Nodes,
+	names: HashMap<String, Id>,
+	frames: Vec<Frame>,
+	line: usize,
+}
+
+impl Reader {
+	#[must_use]
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	const fn unexpected(&self) -> ReadError {
+		ReadError::UnexpectedToken { line: self.line }
+	}
+
+	fn add_name(&mut self, token: &str, id: Id) -> Result<(), ReadError> {
+		let name = token.strip_prefix('%').ok_or_else(|| self.unexpected())?;
+
+		self.names.insert(name.to_string(), id);
+
+		Ok(())
+	}
+
+	fn find_name(&self, token: &str) -> Result<Id, ReadError> {
+		let name = token.strip_prefix('%').ok_or_else(|| self.unexpected())?;
+
+		self.names
+			.get(name)
+			.copied()
+			.ok_or(ReadError::UnknownName { line: self.line })
+	}
+
+	fn read_link(&self, token: &str) -> Result<Link, ReadError> {
+		let (name, port) = match token.split_once(':') {
+			Some((name, port)) => {
+				let port = port.parse().map_err(|_| self.unexpected())?;
+
+				(name, port)
+			}
+			None => (token, 0),
+		};
+
+		let id = self.find_name(name)?;
+
+		Ok(Link { node: id, port })
+	}
+
+	fn read_links(&self, tokens: &[&str]) -> Result<Vec<Link>, ReadError> {
+		tokens.iter().map(|token| self.read_link(token)).collect()
+	}
+
+	fn read_simple(&self, operation: &str, arguments: &[&str]) -> Result<Simple, ReadError> {
+		if operation == "Integer" {
+			let [value] = arguments else {
+				return Err(self.unexpected());
+			};
+			let value = value.parse().map_err(|_| self.unexpected())?;
+
+			return Ok(Simple::Integer { value });
+		}
+
+		let links = self.read_links(arguments)?;
+
+		if operation == "Merge" && !links.is_empty() {
+			return Ok(Simple::Merge { states: links });
+		}
+
+		let simple = match (operation, links.as_slice()) {
+			("NoOp", []) => Simple::NoOp,
+			("Memory", []) => Simple::Memory,
+			("IO", []) => Simple::IO,
+			("Add", &[lhs, rhs]) => Simple::Add { lhs, rhs },
+			("Sub", &[lhs, rhs]) => Simple::Sub { lhs, rhs },
+			("Load", &[state, pointer]) => Simple::Load { state, pointer },
+			("Store", &[state, pointer, value]) => Simple::Store {
+				state,
+				pointer,
+				value,
+			},
+			("Ask", &[state]) => Simple::Ask { state },
+			("Tell", &[state, value]) => Simple::Tell { state, value },
+			_ => return Err(self.unexpected()),
+		};
+
+		Ok(simple)
+	}
+
+	fn current_region(&self) -> Result<Region, ReadError> {
+		self.frames
+			.last()
+			.map(Frame::region)
+			.ok_or(ReadError::UnbalancedBraces { line: self.line })
+	}
+
+	fn open_gamma(&mut self, name: &str, arguments: &[&str]) -> Result<(), ReadError> {
+		let parameters = self.read_links(arguments)?;
+		let region = self.nodes.add_region();
+
+		self.frames.push(Frame::Gamma {
+			name: name.to_string(),
+			parameters,
+			regions: vec![region],
+		});
+
+		Ok(())
+	}
+
+	fn open_theta(&mut self, name: &str, arguments: &[&str]) -> Result<(), ReadError> {
+		let parameters = self.read_links(arguments)?;
+		let (theta, region) = self.nodes.add_theta();
+
+		self.nodes[theta]
+			.as_parameters_mut()
+			.unwrap()
+			.extend(parameters);
+
+		self.add_name(name, theta)?;
+		self.frames.push(Frame::Theta { region });
+
+		Ok(())
+	}
+
+	fn open_region(&mut self) -> Result<(), ReadError> {
+		let region = self.nodes.add_region();
+
+		match self.frames.last_mut() {
+			Some(Frame::Gamma { regions, .. }) => regions.push(region),
+			_ => return Err(ReadError::UnbalancedBraces { line: self.line }),
+		}
+
+		Ok(())
+	}
+
+	fn close_frame(&mut self) -> Result<(), ReadError> {
+		let frame = self
+			.frames
+			.pop()
+			.ok_or(ReadError::UnbalancedBraces { line: self.line })?;
+
+		if let Frame::Gamma {
+			name,
+			parameters,
+			regions,
+		} = frame
+		{
+			let gamma = self.nodes.add_gamma(regions.into());
+
+			self.nodes[gamma]
+				.as_parameters_mut()
+				.unwrap()
+				.extend(parameters);
+
+			self.add_name(&name, gamma)?;
+		}
+
+		Ok(())
+	}
+
+	fn read_end(&mut self, arguments: &[&str]) -> Result<(), ReadError> {
+		let parameters = self.read_links(arguments)?;
+		let region = self.current_region()?;
+
+		self.nodes[region.end()]
+			.as_parameters_mut()
+			.unwrap()
+			.extend(parameters);
+
+		Ok(())
+	}
+
+	fn read_line(&mut self, tokens: &[&str], root: &mut Option<Id>) -> Result<(), ReadError> {
+		match *tokens {
+			[] => {}
+			["return", name] => *root = Some(self.find_name(name)?),
+			["End", ref arguments @ ..] => self.read_end(arguments)?,
+			["}", "{"] => self.open_region()?,
+			["}"] => self.close_frame()?,
+			[name, "=", "Start"] => {
+				let region = self.current_region()?;
+
+				self.add_name(name, region.start())?;
+			}
+			[name, "=", "Gamma", ref arguments @ .., "{"] => self.open_gamma(name, arguments)?,
+			[name, "=", "Theta", ref arguments @ .., "{"] => self.open_theta(name, arguments)?,
+			[name, "=", operation, ref arguments @ ..] => {
+				let simple = self.read_simple(operation, arguments)?;
+				let id = self.nodes.add_simple(simple);
+
+				self.add_name(name, id)?;
+			}
+			_ => return Err(self.unexpected()),
+		}
+
+		Ok(())
+	}
+
+	/// # Errors
+	///
+	/// Returns `ReadError::UnexpectedToken` if a line is malformed.
+	/// Returns `ReadError::UnknownName` if a link refers to an undefined node.
+	/// Returns `ReadError::UnbalancedBraces` if regions are not opened and closed properly.
+	/// Returns `ReadError::MissingReturn` if no root was given.
+	pub fn read(&mut self, source: &str) -> Result<ParseData, ReadError> {
+		let mut root = None;
+
+		self.nodes = Nodes::new();
+		self.names.clear();
+		self.frames.clear();
+
+		for (index, line) in source.lines().enumerate() {
+			let line = line.replace(',', " ");
+			let tokens: Vec<_> = line.split_whitespace().collect();
+
+			self.line = index + 1;
+			self.read_line(&tokens, &mut root)?;
+		}
+
+		if !self.frames.is_empty() {
+			return Err(ReadError::UnbalancedBraces { line: self.line });
+		}
+
+		let io = root.ok_or(ReadError::MissingReturn)?;
+		let nodes = std::mem::take(&mut self.nodes);
+
+		Ok(ParseData::new(nodes, io))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::{collections::HashMap, fmt::Write};
+
+	use regioned::visit::reverse_topological::ReverseTopological;
+
+	use crate::hir::parser::{ParseData, Parser};
+
+	use super::{Printer, ReadError, Reader};
+
+	fn print(data: &ParseData) -> String {
+		let mut output = Vec::new();
+
+		Printer::new()
+			.write(
+				&mut output,
+				data.nodes(),
+				data.roots(),
+				&mut ReverseTopological::new(),
+			)
+			.unwrap();
+
+		String::from_utf8(output).unwrap()
+	}
+
+	// Names are given by the reader, so they are compared in order of use.
+	fn renumber(text: &str) -> String {
+		let mut names = HashMap::new();
+		let mut result = String::new();
+		let mut rest = text;
+
+		while let Some(start) = rest.find('%') {
+			result.push_str(&rest[..=start]);
+			rest = &rest[start + 1..];
+
+			let end = rest
+				.find(|c: char| !c.is_ascii_digit())
+				.unwrap_or(rest.len());
+			let count = names.len();
+			let name = *names.entry(&rest[..end]).or_insert(count);
+
+			write!(result, "{name}").unwrap();
+
+			rest = &rest[end..];
+		}
+
+		result.push_str(rest);
+		result
+	}
+
+	#[test]
+	fn round_trip() {
+		for code in ["", "+-<>.,", "+[->+<]>.", ",[.,]", "+[[-]>[<]],[>+<-]"] {
+			let data = Parser::new().parse(code.char_indices()).unwrap();
+			let text = print(&data);
+			let read = Reader::new().read(&text).unwrap();
+
+			assert_eq!(renumber(&print(&read)), renumber(&text), "`{code}`");
+		}
+	}
+
+	#[test]
+	fn rejects_unbalanced_braces() {
+		let text = "%0 = IO\n%1 = Theta %0 {\n%2 = Start\nreturn %0\n";
+
+		assert!(Reader::new().read(text).is_err());
+	}
+
+	#[test]
+	fn rejects_large_port() {
+		let text = "%0 = IO\n%1 = Integer 0\n%2 = Tell %0:70000, %1\nreturn %2\n";
+
+		assert!(matches!(
+			Reader::new().read(text),
+			Err(ReadError::UnexpectedToken { line: 3 })
+		));
+	}
+}
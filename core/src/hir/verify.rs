@@ -0,0 +1,423 @@
+use std::collections::{HashMap, HashSet};
+
+use regioned::{
+	data_flow::{
+		link::{Id, Link},
+		node::{Compound, Marker, Parameters},
+	},
+	visit::reverse_topological::ReverseTopological,
+};
+
+use super::{
+	data::{find_parents, Kind, Node, Nodes, Simple},
+	kind::Kinds,
+};
+
+#[derive(Debug)]
+pub enum VerifyError {
+	MissingNode {
+		link: Link,
+	},
+	InvalidPort {
+		id: Id,
+		parameter: usize,
+	},
+	EmptyMerge {
+		id: Id,
+	},
+	KindMismatch {
+		id: Id,
+		parameter: usize,
+		expected: Kind,
+		found: Kind,
+	},
+	RegionMismatch {
+		id: Id,
+	},
+	Cycle {
+		id: Id,
+	},
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Visit {
+	Active,
+	Done,
+}
+
+/// Checks that a graph is well formed, so that malformed rewrites are caught
+/// right after the pass that produced them.
+#[derive(Default)]
+pub struct Verifier {
+	kinds: Kinds,
+	parents: HashMap<Id, Id>,
+	existing: HashSet<Id>,
+	visits: HashMap<Id, Visit>,
+}
+
+fn dependencies_of(nodes: &Nodes, id: Id) -> Vec<Link> {
+	let mut list: Vec<_> = nodes[id].parameters().copied().collect();
+
+	if let Node::Compound(compound) = &nodes[id] {
+		for region in compound.regions() {
+			list.push(region.start().into());
+			list.push(region.end().into());
+		}
+	}
+
+	list
+}
+
+impl Verifier {
+	#[must_use]
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	fn visit(&mut self, nodes: &Nodes, stack: &mut Vec<(Id, Vec<Link>)>, id: Id) {
+		self.visits.insert(id, Visit::Active);
+
+		stack.push((id, dependencies_of(nodes, id)));
+	}
+
+	// Theta back edges are implicit, so any cycle at all is an error.
+	fn find_cycles<I>(&mut self, nodes: &Nodes, roots: I) -> Result<(), VerifyError>
+	where
+		I: IntoIterator<Item = Id>,
+	{
+		let mut stack = Vec::new();
+
+		for root in roots {
+			if !self.existing.contains(&root) {
+				return Err(VerifyError::MissingNode { link: root.into() });
+			}
+
+			if self.visits.contains_key(&root) {
+				continue;
+			}
+
+			self.visit(nodes, &mut stack, root);
+
+			while let Some((id, dependencies)) = stack.last_mut() {
+				let id = *id;
+				let Some(next) = dependencies.pop() else {
+					self.visits.insert(id, Visit::Done);
+					stack.pop();
+
+					continue;
+				};
+
+				if !self.existing.contains(&next.node) {
+					return Err(VerifyError::MissingNode { link: next });
+				}
+
+				match self.visits.get(&next.node) {
+					Some(Visit::Active) => return Err(VerifyError::Cycle { id: next.node }),
+					Some(Visit::Done) => {}
+					None => self.visit(nodes, &mut stack, next.node),
+				}
+			}
+		}
+
+		Ok(())
+	}
+
+	// Kinds and port counts assume every compound has its condition and at
+	// least one region, so this runs before either is looked at.
+	fn check_arity(nodes: &Nodes, id: Id) -> Result<(), VerifyError> {
+		let valid = match &nodes[id] {
+			Node::Compound(Compound::Gamma {
+				parameters,
+				regions,
+			}) => !parameters.is_empty() && !regions.is_empty(),
+			Node::Compound(Compound::Theta { parameters, region }) => {
+				nodes[region.end()].parameters().len() == parameters.len() + 1
+			}
+			_ => true,
+		};
+
+		if valid {
+			Ok(())
+		} else {
+			Err(VerifyError::RegionMismatch { id })
+		}
+	}
+
+	fn result_count(&self, nodes: &Nodes, id: Id) -> usize {
+		match &nodes[id] {
+			Node::Simple(simple) => simple.results().len(),
+			Node::Marker(Marker::Start) => match &nodes[self.parents[&id]] {
+				Node::Compound(Compound::Gamma { parameters, .. }) => {
+					parameters.len().saturating_sub(1)
+				}
+				Node::Compound(Compound::Theta { parameters, .. }) => parameters.len(),
+				_ => 0,
+			},
+			Node::Marker(Marker::End { .. }) => 0,
+			Node::Compound(Compound::Gamma { regions, .. }) => regions
+				.first()
+				.map_or(0, |region| nodes[region.end()].parameters().len()),
+			Node::Compound(Compound::Theta { region, .. }) => {
+				nodes[region.end()].parameters().len().saturating_sub(1)
+			}
+			Node::Compound(_) => 0,
+		}
+	}
+
+	fn check_ports(&self, nodes: &Nodes, id: Id) -> Result<(), VerifyError> {
+		for (parameter, link) in nodes[id].parameters().enumerate() {
+			if usize::from(link.port) >= self.result_count(nodes, link.node) {
+				return Err(VerifyError::InvalidPort { id, parameter });
+			}
+		}
+
+		Ok(())
+	}
+
+	fn check_kinds<I>(&self, id: Id, links: &[Link], expected: I) -> Result<(), VerifyError>
+	where
+		I: IntoIterator<Item = Kind>,
+	{
+		for (parameter, (link, expected)) in links.iter().zip(expected).enumerate() {
+			let found = self.kinds.get(*link);
+
+			if found != expected {
+				return Err(VerifyError::KindMismatch {
+					id,
+					parameter,
+					expected,
+					found,
+				});
+			}
+		}
+
+		Ok(())
+	}
+
+	fn check_gamma(
+		&self,
+		id: Id,
+		parameters: &[Link],
+		ends: &[Vec<Link>],
+	) -> Result<(), VerifyError> {
+		let Some(condition) = parameters.last() else {
+			return Err(VerifyError::RegionMismatch { id });
+		};
+
+		self.check_kinds(id, &[*condition], [Kind::Value])?;
+
+		let Some((first, rest)) = ends.split_first() else {
+			return Err(VerifyError::RegionMismatch { id });
+		};
+
+		if rest.iter().any(|end| end.len() != first.len()) {
+			return Err(VerifyError::RegionMismatch { id });
+		}
+
+		let expected: Vec<_> = first.iter().map(|link| self.kinds.get(*link)).collect();
+
+		rest.iter()
+			.try_for_each(|end| self.check_kinds(id, end, expected.iter().copied()))
+	}
+
+	fn check_theta(&self, id: Id, parameters: &[Link], end: &[Link]) -> Result<(), VerifyError> {
+		let expected = parameters.iter().map(|link| self.kinds.get(*link));
+
+		self.check_kinds(id, end, expected.chain([Kind::Value]))
+	}
+
+	fn check_node(&self, nodes: &Nodes, id: Id) -> Result<(), VerifyError> {
+		self.check_ports(nodes, id)?;
+
+		let end_of = |end: Id| -> Vec<Link> { nodes[end].parameters().copied().collect() };
+
+		match &nodes[id] {
+			Node::Simple(simple) => {
+				if matches!(simple, Simple::Merge { states } if states.is_empty()) {
+					return Err(VerifyError::EmptyMerge { id });
+				}
+
+				let links: Vec<_> = simple.parameters().copied().collect();
+
+				self.check_kinds(id, &links, simple.arguments())
+			}
+			Node::Marker(_) => Ok(()),
+			Node::Compound(Compound::Gamma {
+				parameters,
+				regions,
+			}) => {
+				let ends: Vec<_> = regions.iter().map(|region| end_of(region.end())).collect();
+
+				self.check_gamma(id, parameters, &ends)
+			}
+			Node::Compound(Compound::Theta { parameters, region }) => {
+				self.check_theta(id, parameters, &end_of(region.end()))
+			}
+			Node::Compound(_) => Ok(()),
+		}
+	}
+
+	/// # Errors
+	///
+	/// Returns `VerifyError::MissingNode` with the dangling link if a node refers to a node that does not exist.
+	/// Returns `VerifyError::Cycle` if the graph has a cycle.
+	/// Returns `VerifyError::InvalidPort` if a link refers to a port its node does not have.
+	/// Returns `VerifyError::EmptyMerge` if a `Merge` has no states.
+	/// Returns `VerifyError::KindMismatch` if a state feeds a value input or vice versa.
+	/// Returns `VerifyError::RegionMismatch` if a compound lacks its condition or regions, or if they disagree with it.
+	pub fn run<I>(
+		&mut self,
+		nodes: &Nodes,
+		roots: I,
+		topological: &mut ReverseTopological,
+	) -> Result<(), VerifyError>
+	where
+		I: IntoIterator<Item = Id> + Clone,
+	{
+		self.existing.clear();
+		self.existing.extend(nodes.iter().map(|entry| entry.0));
+		self.visits.clear();
+
+		self.find_cycles(nodes, roots.clone())?;
+
+		let mut reached: Vec<_> = self.visits.keys().copied().collect();
+
+		reached.sort_unstable();
+		reached
+			.iter()
+			.try_for_each(|&id| Self::check_arity(nodes, id))?;
+
+		find_parents(nodes, &mut self.parents);
+
+		self.kinds.run(nodes, roots, topological);
+
+		reached
+			.into_iter()
+			.try_for_each(|id| self.check_node(nodes, id))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use regioned::{
+		data_flow::{link::Link, node::Compound},
+		visit::reverse_topological::ReverseTopological,
+	};
+
+	use crate::hir::{
+		data::{Node, Simple},
+		parser::{ParseData, Parser},
+		text::Reader,
+	};
+
+	use super::{Verifier, VerifyError};
+
+	fn verify(data: &ParseData) -> Result<(), VerifyError> {
+		Verifier::new().run(data.nodes(), data.roots(), &mut ReverseTopological::new())
+	}
+
+	fn read(text: &str) -> ParseData {
+		Reader::new().read(text).unwrap()
+	}
+
+	fn tell_value(data: &mut ParseData) -> &mut Link {
+		let root = data.roots()[0];
+		let Node::Simple(Simple::Tell { value, .. }) = &mut data.nodes_mut()[root] else {
+			unreachable!()
+		};
+
+		value
+	}
+
+	#[test]
+	fn accepts_parsed() {
+		for code in ["", "+>-<.,", "+[->+<]", ",[.,]", "+[[-]>[<]]"] {
+			let data = Parser::new().parse(code.char_indices()).unwrap();
+
+			assert!(verify(&data).is_ok(), "`{code}`");
+		}
+	}
+
+	#[test]
+	fn reports_missing_link() {
+		let mut data = read("%0 = IO\n%1 = Integer 1\n%2 = Tell %0, %1\nreturn %2\n");
+		let dangling = Link::from(100);
+
+		*tell_value(&mut data) = dangling;
+
+		assert!(matches!(
+			verify(&data),
+			Err(VerifyError::MissingNode { link }) if link == dangling
+		));
+	}
+
+	#[test]
+	fn reports_cycle() {
+		let mut data =
+			read("%0 = IO\n%1 = Integer 1\n%2 = Add %1, %1\n%3 = Tell %0, %2\nreturn %3\n");
+		let add = tell_value(&mut data).node;
+		let Node::Simple(Simple::Add { lhs, .. }) = &mut data.nodes_mut()[add] else {
+			unreachable!()
+		};
+
+		*lhs = add.into();
+
+		assert!(matches!(verify(&data), Err(VerifyError::Cycle { .. })));
+	}
+
+	#[test]
+	fn reports_invalid_port() {
+		let data = read("%0 = IO\n%1 = Integer 1\n%2 = Tell %0, %1:1\nreturn %2\n");
+
+		assert!(matches!(
+			verify(&data),
+			Err(VerifyError::InvalidPort { parameter: 1, .. })
+		));
+	}
+
+	#[test]
+	fn reports_kind_mismatch() {
+		let data = read("%0 = IO\n%1 = Tell %0, %0\nreturn %1\n");
+
+		assert!(matches!(
+			verify(&data),
+			Err(VerifyError::KindMismatch { parameter: 1, .. })
+		));
+	}
+
+	#[test]
+	fn reports_gamma_without_condition() {
+		let data = read("%0 = Gamma {\n%1 = Start\nEnd\n}\nreturn %0\n");
+
+		assert!(matches!(
+			verify(&data),
+			Err(VerifyError::RegionMismatch { id }) if id == data.roots()[0]
+		));
+	}
+
+	#[test]
+	fn reports_gamma_without_regions() {
+		let mut data = read("%0 = Integer 0\n%1 = Gamma %0 {\nEnd\n}\nreturn %1\n");
+		let root = data.roots()[0];
+		let Node::Compound(Compound::Gamma { regions, .. }) = &mut data.nodes_mut()[root] else {
+			unreachable!()
+		};
+
+		*regions = Default::default();
+
+		assert!(matches!(
+			verify(&data),
+			Err(VerifyError::RegionMismatch { id }) if id == root
+		));
+	}
+
+	#[test]
+	fn reports_theta_without_condition() {
+		let data = read("%0 = Theta {\n%1 = Start\nEnd\n}\nreturn %0\n");
+
+		assert!(matches!(
+			verify(&data),
+			Err(VerifyError::RegionMismatch { id }) if id == data.roots()[0]
+		));
+	}
+}
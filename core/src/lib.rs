@@ -0,0 +1,3 @@
+pub mod codegen;
+pub mod hir;
+pub mod mir;
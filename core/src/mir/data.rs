@@ -0,0 +1,194 @@
+#[derive(Debug)]
+pub struct Program {
+	bodies: Box<[Box<[Instruction]>]>,
+	locals: usize,
+}
+
+impl Program {
+	#[must_use]
+	pub const fn new(bodies: Box<[Box<[Instruction]>]>, locals: usize) -> Self {
+		Self { bodies, locals }
+	}
+
+	#[must_use]
+	pub fn bodies(&self) -> &[Box<[Instruction]>] {
+		&self.bodies
+	}
+
+	#[must_use]
+	pub fn bodies_mut(&mut self) -> &mut [Box<[Instruction]>] {
+		&mut self.bodies
+	}
+
+	#[must_use]
+	pub const fn locals(&self) -> usize {
+		self.locals
+	}
+
+	pub fn set_locals(&mut self, locals: usize) {
+		self.locals = locals;
+	}
+}
+
+#[derive(Debug)]
+pub enum Instruction {
+	Memory {
+		result: u32,
+	},
+
+	IO {
+		result: u32,
+	},
+
+	Integer {
+		result: u32,
+		value: u64,
+	},
+
+	Move {
+		from: u32,
+		to: u32,
+	},
+
+	Add {
+		result: u32,
+		lhs: u32,
+		rhs: u32,
+	},
+
+	Sub {
+		result: u32,
+		lhs: u32,
+		rhs: u32,
+	},
+
+	Load {
+		result: u32,
+		pointer: u32,
+		state: Option<u32>,
+	},
+
+	Store {
+		pointer: u32,
+		value: u32,
+		state: Option<u32>,
+	},
+
+	Ask {
+		result: u32,
+		state: Option<u32>,
+	},
+
+	Tell {
+		value: u32,
+		state: Option<u32>,
+	},
+
+	Select {
+		condition: u32,
+		code: Box<[usize]>,
+	},
+
+	Repeat {
+		code: usize,
+		condition: u32,
+	},
+}
+
+impl Instruction {
+	/// Returns the registers read by this instruction. The condition of a
+	/// `Select` is read before its bodies, while that of a `Repeat` is read
+	/// after each iteration of its body.
+	#[must_use]
+	pub fn reads(&self) -> Vec<u32> {
+		let mut list = match *self {
+			Self::Memory { .. } | Self::IO { .. } | Self::Integer { .. } | Self::Ask { .. } => {
+				Vec::new()
+			}
+			Self::Move { from, .. } => vec![from],
+			Self::Add { lhs, rhs, .. } | Self::Sub { lhs, rhs, .. } => vec![lhs, rhs],
+			Self::Load { pointer, .. } => vec![pointer],
+			Self::Store { pointer, value, .. } => vec![pointer, value],
+			Self::Tell { value, .. } => vec![value],
+			Self::Select { condition, .. } | Self::Repeat { condition, .. } => vec![condition],
+		};
+
+		list.extend(self.state());
+		list
+	}
+
+	/// Returns mutable references to the registers read by this instruction.
+	pub fn reads_mut(&mut self) -> Vec<&mut u32> {
+		match self {
+			Self::Memory { .. } | Self::IO { .. } | Self::Integer { .. } => Vec::new(),
+			Self::Move { from, .. } => vec![from],
+			Self::Add { lhs, rhs, .. } | Self::Sub { lhs, rhs, .. } => vec![lhs, rhs],
+			Self::Load { pointer, state, .. } => [Some(pointer), state.as_mut()]
+				.into_iter()
+				.flatten()
+				.collect(),
+			Self::Store {
+				pointer,
+				value,
+				state,
+			} => [Some(pointer), Some(value), state.as_mut()]
+				.into_iter()
+				.flatten()
+				.collect(),
+			Self::Ask { state, .. } => state.iter_mut().collect(),
+			Self::Tell { value, state } => [Some(value), state.as_mut()]
+				.into_iter()
+				.flatten()
+				.collect(),
+			Self::Select { condition, .. } | Self::Repeat { condition, .. } => vec![condition],
+		}
+	}
+
+	/// Returns the state register threaded through this instruction, if it
+	/// was retained during sequencing.
+	#[must_use]
+	pub const fn state(&self) -> Option<u32> {
+		match *self {
+			Self::Load { state, .. }
+			| Self::Store { state, .. }
+			| Self::Ask { state, .. }
+			| Self::Tell { state, .. } => state,
+			_ => None,
+		}
+	}
+
+	/// Returns the register written by this instruction, if any.
+	#[must_use]
+	pub const fn writes(&self) -> Option<u32> {
+		match *self {
+			Self::Memory { result }
+			| Self::IO { result }
+			| Self::Integer { result, .. }
+			| Self::Add { result, .. }
+			| Self::Sub { result, .. }
+			| Self::Load { result, .. }
+			| Self::Ask { result, .. }
+			| Self::Move { to: result, .. } => Some(result),
+			Self::Store { .. } | Self::Tell { .. } | Self::Select { .. } | Self::Repeat { .. } => {
+				None
+			}
+		}
+	}
+
+	/// Returns a mutable reference to the register written by this instruction, if any.
+	pub fn writes_mut(&mut self) -> Option<&mut u32> {
+		match self {
+			Self::Memory { result }
+			| Self::IO { result }
+			| Self::Integer { result, .. }
+			| Self::Add { result, .. }
+			| Self::Sub { result, .. }
+			| Self::Load { result, .. }
+			| Self::Ask { result, .. }
+			| Self::Move { to: result, .. } => Some(result),
+			Self::Store { .. } | Self::Tell { .. } | Self::Select { .. } | Self::Repeat { .. } => {
+				None
+			}
+		}
+	}
+}
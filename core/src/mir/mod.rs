@@ -0,0 +1,7 @@
+pub mod data;
+pub mod peephole;
+pub mod registers;
+pub mod scheduler;
+pub mod sequencer;
+pub mod text;
+pub mod verify;
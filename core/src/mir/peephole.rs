@@ -0,0 +1,409 @@
+use std::collections::HashMap;
+
+use super::data::{Instruction, Program};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Fact {
+	Copy(u32),
+	Constant(u64),
+}
+
+type Facts = HashMap<u32, Fact>;
+
+fn kill(facts: &mut Facts, register: u32) {
+	facts.remove(&register);
+	facts.retain(|_, fact| *fact != Fact::Copy(register));
+}
+
+fn add_writes(bodies: &[Box<[Instruction]>], code: usize, list: &mut Vec<u32>) {
+	for insn in bodies[code].iter() {
+		list.extend(insn.writes());
+
+		match insn {
+			Instruction::Select { code, .. } => {
+				code.iter().for_each(|&code| add_writes(bodies, code, list));
+			}
+			Instruction::Repeat { code, .. } => add_writes(bodies, *code, list),
+			_ => {}
+		}
+	}
+}
+
+const fn has_effect(insn: &Instruction) -> bool {
+	matches!(
+		insn,
+		Instruction::Store { .. }
+			| Instruction::Ask { .. }
+			| Instruction::Tell { .. }
+			| Instruction::Select { .. }
+			| Instruction::Repeat { .. }
+	)
+}
+
+fn index_of(register: u32) -> usize {
+	usize::try_from(register).unwrap()
+}
+
+fn join(into: &mut [bool], from: &[bool]) {
+	into.iter_mut().zip(from).for_each(|(a, b)| *a |= b);
+}
+
+/// Cleans up the moves and constants left behind by sequencing.
+/// Copies are propagated into their uses, moves and constants that
+/// are already in place are removed, moves of constants are turned back
+/// into constants, and instructions with unused results are dropped.
+/// Registers are then renumbered so that unused ones are no longer counted.
+#[derive(Default)]
+pub struct Peephole {
+	changed: bool,
+}
+
+impl Peephole {
+	#[must_use]
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	fn propagate_integer(
+		&mut self,
+		result: u32,
+		value: u64,
+		facts: &mut Facts,
+	) -> Option<Instruction> {
+		if facts.get(&result) == Some(&Fact::Constant(value)) {
+			self.changed = true;
+
+			return None;
+		}
+
+		kill(facts, result);
+		facts.insert(result, Fact::Constant(value));
+
+		Some(Instruction::Integer { result, value })
+	}
+
+	fn replace_copy(&mut self, register: &mut u32, facts: &Facts) {
+		if let Some(&Fact::Copy(source)) = facts.get(register) {
+			*register = source;
+			self.changed = true;
+		}
+	}
+
+	fn propagate_insn(
+		&mut self,
+		bodies: &mut [Box<[Instruction]>],
+		mut insn: Instruction,
+		facts: &mut Facts,
+	) -> Option<Instruction> {
+		// The condition of a `Repeat` is read after its body, so it is
+		// rewritten with the facts that hold there instead.
+		if !matches!(insn, Instruction::Repeat { .. }) {
+			for register in insn.reads_mut() {
+				self.replace_copy(register, facts);
+			}
+		}
+
+		match insn {
+			Instruction::Integer { result, value } => {
+				return self.propagate_integer(result, value, facts);
+			}
+			Instruction::Move { from, to } => {
+				if let Some(&Fact::Constant(value)) = facts.get(&from) {
+					self.changed = true;
+
+					return self.propagate_integer(to, value, facts);
+				}
+
+				if from == to || facts.get(&to) == Some(&Fact::Copy(from)) {
+					self.changed = true;
+
+					return None;
+				}
+
+				kill(facts, to);
+				facts.insert(to, Fact::Copy(from));
+			}
+			Instruction::Select { ref code, .. } => {
+				let mut written = Vec::new();
+
+				for &inner in code.iter() {
+					self.propagate_body(bodies, inner, &mut facts.clone());
+
+					add_writes(bodies, inner, &mut written);
+				}
+
+				written
+					.into_iter()
+					.for_each(|register| kill(facts, register));
+			}
+			// Only facts untouched by the body hold at the start of every
+			// iteration, and the facts at the end of the body hold on exit.
+			Instruction::Repeat {
+				code,
+				ref mut condition,
+			} => {
+				let mut written = Vec::new();
+
+				add_writes(bodies, code, &mut written);
+				written
+					.into_iter()
+					.for_each(|register| kill(facts, register));
+
+				self.propagate_body(bodies, code, facts);
+				self.replace_copy(condition, facts);
+			}
+			_ => {
+				if let Some(result) = insn.writes() {
+					kill(facts, result);
+				}
+			}
+		}
+
+		Some(insn)
+	}
+
+	fn propagate_body(
+		&mut self,
+		bodies: &mut [Box<[Instruction]>],
+		code: usize,
+		facts: &mut Facts,
+	) {
+		let list = std::mem::take(&mut bodies[code]).into_vec();
+		let mut result = Vec::with_capacity(list.len());
+
+		for insn in list {
+			if let Some(insn) = self.propagate_insn(bodies, insn, facts) {
+				result.push(insn);
+			}
+		}
+
+		bodies[code] = result.into();
+	}
+
+	fn eliminate_repeat(
+		&mut self,
+		bodies: &mut [Box<[Instruction]>],
+		code: usize,
+		condition: u32,
+		live: &mut Vec<bool>,
+		remove: bool,
+	) {
+		let mut exit = live.clone();
+
+		exit[index_of(condition)] = true;
+
+		let mut last = exit.clone();
+
+		loop {
+			let mut entry = last.clone();
+
+			self.eliminate_body(bodies, code, &mut entry, false);
+
+			let mut next = exit.clone();
+
+			join(&mut next, &entry);
+
+			if next == last {
+				break;
+			}
+
+			last = next;
+		}
+
+		self.eliminate_body(bodies, code, &mut last, remove);
+
+		*live = last;
+	}
+
+	fn eliminate_insn(
+		&mut self,
+		bodies: &mut [Box<[Instruction]>],
+		insn: &Instruction,
+		live: &mut Vec<bool>,
+		remove: bool,
+	) -> bool {
+		match insn {
+			Instruction::Select { condition, code } => {
+				let mut joined = vec![false; live.len()];
+
+				for &inner in code.iter() {
+					let mut branch = live.clone();
+
+					self.eliminate_body(bodies, inner, &mut branch, remove);
+
+					join(&mut joined, &branch);
+				}
+
+				joined[index_of(*condition)] = true;
+
+				*live = joined;
+			}
+			Instruction::Repeat { code, condition } => {
+				self.eliminate_repeat(bodies, *code, *condition, live, remove);
+			}
+			_ => {
+				let result = insn.writes();
+
+				if !has_effect(insn) && result.is_some_and(|result| !live[index_of(result)]) {
+					return false;
+				}
+
+				if let Some(result) = result {
+					live[index_of(result)] = false;
+				}
+
+				for register in insn.reads() {
+					live[index_of(register)] = true;
+				}
+			}
+		}
+
+		true
+	}
+
+	// Walks a body backwards, turning the registers live after it into
+	// the registers live before it.
+	fn eliminate_body(
+		&mut self,
+		bodies: &mut [Box<[Instruction]>],
+		code: usize,
+		live: &mut Vec<bool>,
+		remove: bool,
+	) {
+		let list = std::mem::take(&mut bodies[code]).into_vec();
+		let mut result = Vec::with_capacity(list.len());
+
+		for insn in list.into_iter().rev() {
+			let used = self.eliminate_insn(bodies, &insn, live, remove);
+
+			if used || !remove {
+				result.push(insn);
+			} else {
+				self.changed = true;
+			}
+		}
+
+		result.reverse();
+
+		bodies[code] = result.into();
+	}
+
+	fn compact(program: &mut Program) {
+		let mut map = vec![None; program.locals()];
+		let mut count = 0;
+		let mut rename = |register: &mut u32| {
+			let entry = map[index_of(*register)].get_or_insert_with(|| {
+				count += 1;
+
+				count - 1
+			});
+
+			*register = *entry;
+		};
+
+		for body in program.bodies_mut() {
+			for insn in body.iter_mut() {
+				insn.reads_mut().into_iter().for_each(&mut rename);
+
+				if let Some(register) = insn.writes_mut() {
+					rename(register);
+				}
+			}
+		}
+
+		program.set_locals(index_of(count));
+	}
+
+	/// Runs all rewrites until none of them apply.
+	pub fn run(&mut self, program: &mut Program) {
+		let locals = program.locals();
+
+		if program.bodies().is_empty() {
+			return;
+		}
+
+		loop {
+			let bodies = program.bodies_mut();
+
+			self.changed = false;
+
+			self.propagate_body(bodies, 0, &mut Facts::new());
+			self.eliminate_body(bodies, 0, &mut vec![false; locals], true);
+
+			if !self.changed {
+				break;
+			}
+		}
+
+		Self::compact(program);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::mir::{
+		data::Program,
+		text::{write, Reader},
+	};
+
+	use super::Peephole;
+
+	fn read(text: &str) -> Program {
+		Reader::new().read(text).unwrap()
+	}
+
+	fn print(program: &Program) -> String {
+		let mut output = Vec::new();
+
+		write(&mut output, program).unwrap();
+
+		String::from_utf8(output).unwrap()
+	}
+
+	fn assert_rewrites(input: &str, expected: &str) {
+		let mut program = read(input);
+
+		Peephole::new().run(&mut program);
+
+		assert_eq!(print(&program), print(&read(expected)));
+	}
+
+	#[test]
+	fn keeps_copy_written_in_repeat() {
+		assert_rewrites(
+			"r0 = ask\nr1 = move r0\nrepeat {\nr0 = ask\ntell r1\n} while r0\n",
+			"r0 = ask\nr1 = move r0\nrepeat {\nr0 = ask\ntell r1\n} while r0\n",
+		);
+	}
+
+	#[test]
+	fn reads_repeat_condition_after_body() {
+		assert_rewrites(
+			"r0 = ask\nr1 = move r0\nrepeat {\ntell r1\nr1 = ask\nr2 = move r1\n} while r2\n",
+			"r0 = ask\nr1 = move r0\nrepeat {\ntell r1\nr1 = ask\n} while r1\n",
+		);
+	}
+
+	#[test]
+	fn removes_dead_code_in_one_branch() {
+		assert_rewrites(
+			"r0 = ask\nr1 = integer 1\nselect r0 {\nr2 = add r0, r1\ntell r2\n} {\nr3 = sub r0, r1\n}\n",
+			"r0 = ask\nr1 = integer 1\nselect r0 {\nr2 = add r0, r1\ntell r2\n} {\n}\n",
+		);
+	}
+
+	#[test]
+	fn compacts_locals() {
+		let mut program = read("r5 = ask\nr2 = integer 7\nr9 = add r5, r2\ntell r9\n");
+
+		Peephole::new().run(&mut program);
+
+		assert_eq!(program.locals(), 3);
+		assert_eq!(
+			print(&program),
+			print(&read(
+				"r0 = ask\nr1 = integer 7\nr2 = add r0, r1\ntell r2\n"
+			))
+		);
+	}
+}
@@ -0,0 +1,452 @@
+use std::collections::HashSet;
+
+use regioned::{
+	data_flow::{
+		link::{Id, Link},
+		node::Parameters,
+	},
+	visit::{reverse_topological::ReverseTopological, successors::Successors},
+};
+
+use crate::hir::data::Nodes;
+
+use super::data::{Instruction, Program};
+
+/// Hands out registers to the results of nodes while sequencing. Every use
+/// of a result is fetched exactly once, in the order it is sequenced.
+pub trait Allocator {
+	/// Prepares for sequencing the graph reachable from the roots.
+	fn reset<I>(&mut self, nodes: &Nodes, roots: I, topological: &mut ReverseTopological)
+	where
+		I: IntoIterator<Item = Id>;
+
+	/// Returns the registers assigned to results so far.
+	fn assigned(&self) -> &ResultMap;
+
+	/// Returns the number of registers handed out so far.
+	fn register_count(&self) -> usize;
+
+	/// Returns the register of a result and marks one of its uses as done.
+	fn fetch(&mut self, link: Link) -> u32;
+
+	/// Assigns an already handed out register to a result.
+	fn reuse(&mut self, nodes: &Nodes, link: Link, register: u32);
+
+	/// Assigns a register that holds nothing live to a result.
+	fn reserve(&mut self, nodes: &Nodes, link: Link) -> u32;
+
+	/// Assigns the preferred register to a result if it holds nothing live,
+	/// otherwise behaves like `reserve`.
+	fn reuse_or_reserve(&mut self, nodes: &Nodes, link: Link, preferred: u32) -> u32;
+
+	/// Rewrites the sequenced program once all registers are handed out.
+	fn finish(&mut self, program: &mut Program);
+}
+
+#[derive(Default)]
+pub struct ResultMap {
+	results: Vec<Vec<u32>>,
+}
+
+impl ResultMap {
+	fn reset(&mut self, nodes: &Nodes) {
+		let active = nodes.active();
+
+		self.results.iter_mut().for_each(Vec::clear);
+
+		if self.results.len() < active {
+			self.results.resize_with(active, Vec::new);
+		}
+	}
+
+	pub fn get(&self, link: Link) -> u32 {
+		let index = usize::from(link.port);
+
+		self.results[link.node][index]
+	}
+
+	pub fn set(&mut self, link: Link, register: u32) {
+		let index = usize::from(link.port);
+		let list = &mut self.results[link.node];
+
+		if index >= list.len() {
+			list.resize(index + 1, 0);
+		}
+
+		list[index] = register;
+	}
+}
+
+/// Hands out the first register that holds nothing live, so registers are
+/// decided on the spot without looking at the rest of the program.
+#[derive(Default)]
+pub struct Registers {
+	successors: Successors,
+
+	assigned: ResultMap,
+	remaining: Vec<usize>,
+}
+
+impl Registers {
+	#[must_use]
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	fn references_count(&self, nodes: &Nodes, value: Link) -> usize {
+		let successors = self.successors.cache()[value.node].iter();
+
+		successors
+			.flat_map(|&id| nodes[id].parameters())
+			.filter(|&&link| link == value)
+			.count()
+	}
+
+	fn next_available(&mut self) -> u32 {
+		let inactive = self.remaining.iter().position(|&count| count == 0);
+		let register = inactive.unwrap_or_else(|| {
+			self.remaining.push(0);
+			self.remaining.len() - 1
+		});
+
+		register.try_into().unwrap()
+	}
+}
+
+impl Allocator for Registers {
+	fn reset<I>(&mut self, nodes: &Nodes, roots: I, topological: &mut ReverseTopological)
+	where
+		I: IntoIterator<Item = Id>,
+	{
+		self.successors.run(nodes, roots, topological);
+		self.assigned.reset(nodes);
+		self.remaining.clear();
+	}
+
+	fn assigned(&self) -> &ResultMap {
+		&self.assigned
+	}
+
+	fn register_count(&self) -> usize {
+		self.remaining.len()
+	}
+
+	fn fetch(&mut self, link: Link) -> u32 {
+		let register = self.assigned.get(link);
+		let index = usize::try_from(register).unwrap();
+
+		self.remaining[index] -= 1;
+
+		register
+	}
+
+	fn reuse(&mut self, nodes: &Nodes, link: Link, register: u32) {
+		let index = usize::try_from(register).unwrap();
+
+		self.assigned.set(link, register);
+
+		self.remaining[index] += self.references_count(nodes, link);
+	}
+
+	fn reserve(&mut self, nodes: &Nodes, link: Link) -> u32 {
+		let register = self.next_available();
+
+		self.reuse(nodes, link, register);
+
+		register
+	}
+
+	fn reuse_or_reserve(&mut self, nodes: &Nodes, link: Link, preferred: u32) -> u32 {
+		let index = usize::try_from(preferred).unwrap();
+
+		if self.remaining[index] == 0 {
+			self.reuse(nodes, link, preferred);
+
+			preferred
+		} else {
+			self.reserve(nodes, link)
+		}
+	}
+
+	fn finish(&mut self, _program: &mut Program) {}
+}
+
+fn index_of(register: u32) -> usize {
+	usize::try_from(register).unwrap()
+}
+
+/// Gives every result a register of its own, then merges the registers on
+/// both sides of a `Move` whenever they are never live at the same time and
+/// colors what remains. Loop carried values and `Gamma` results end up in
+/// the same register as the values moved into them, so their moves vanish.
+#[derive(Default)]
+pub struct Coalescing {
+	assigned: ResultMap,
+	count: u32,
+
+	interference: Vec<HashSet<u32>>,
+	parents: Vec<u32>,
+}
+
+impl Coalescing {
+	#[must_use]
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	fn find(&self, mut register: u32) -> u32 {
+		while self.parents[index_of(register)] != register {
+			register = self.parents[index_of(register)];
+		}
+
+		register
+	}
+
+	// A `Move` does not make its result interfere with its source, as both
+	// hold the same value afterwards.
+	fn add_interference(&mut self, live: &[bool], result: u32, except: Option<u32>) {
+		let iter = live.iter().enumerate().filter(|entry| *entry.1);
+
+		for register in iter.map(|entry| u32::try_from(entry.0).unwrap()) {
+			if register == result || Some(register) == except {
+				continue;
+			}
+
+			self.interference[index_of(result)].insert(register);
+			self.interference[index_of(register)].insert(result);
+		}
+	}
+
+	fn live_repeat(
+		&mut self,
+		bodies: &[Box<[Instruction]>],
+		code: usize,
+		condition: u32,
+		live: &mut Vec<bool>,
+		record: bool,
+	) {
+		let mut exit = live.clone();
+
+		exit[index_of(condition)] = true;
+
+		let mut last = exit.clone();
+
+		loop {
+			let mut entry = last.clone();
+
+			self.live_body(bodies, code, &mut entry, false);
+
+			let mut next = exit.clone();
+
+			next.iter_mut().zip(entry).for_each(|(a, b)| *a |= b);
+
+			if next == last {
+				break;
+			}
+
+			last = next;
+		}
+
+		self.live_body(bodies, code, &mut last, record);
+
+		*live = last;
+	}
+
+	fn live_insn(
+		&mut self,
+		bodies: &[Box<[Instruction]>],
+		insn: &Instruction,
+		live: &mut Vec<bool>,
+		record: bool,
+	) {
+		match *insn {
+			Instruction::Select {
+				condition,
+				ref code,
+			} => {
+				let mut joined = vec![false; live.len()];
+
+				for &inner in code.iter() {
+					let mut branch = live.clone();
+
+					self.live_body(bodies, inner, &mut branch, record);
+
+					joined.iter_mut().zip(branch).for_each(|(a, b)| *a |= b);
+				}
+
+				joined[index_of(condition)] = true;
+
+				*live = joined;
+			}
+			Instruction::Repeat { code, condition } => {
+				self.live_repeat(bodies, code, condition, live, record);
+			}
+			_ => {
+				if let Some(result) = insn.writes() {
+					if record {
+						let except = match *insn {
+							Instruction::Move { from, .. } => Some(from),
+							_ => None,
+						};
+
+						self.add_interference(live, result, except);
+					}
+
+					live[index_of(result)] = false;
+				}
+
+				for register in insn.reads() {
+					live[index_of(register)] = true;
+				}
+			}
+		}
+	}
+
+	fn live_body(
+		&mut self,
+		bodies: &[Box<[Instruction]>],
+		code: usize,
+		live: &mut Vec<bool>,
+		record: bool,
+	) {
+		for insn in bodies[code].iter().rev() {
+			self.live_insn(bodies, insn, live, record);
+		}
+	}
+
+	fn interferes(&self, lhs: u32, rhs: u32) -> bool {
+		self.interference[index_of(lhs)]
+			.iter()
+			.any(|&register| self.find(register) == rhs)
+	}
+
+	fn coalesce(&mut self, bodies: &[Box<[Instruction]>]) {
+		for insn in bodies.iter().flat_map(|body| body.iter()) {
+			let Instruction::Move { from, to } = *insn else {
+				continue;
+			};
+
+			let from = self.find(from);
+			let to = self.find(to);
+
+			if from == to || self.interferes(from, to) {
+				continue;
+			}
+
+			let moved = std::mem::take(&mut self.interference[index_of(to)]);
+
+			self.interference[index_of(from)].extend(moved);
+			self.parents[index_of(to)] = from;
+		}
+	}
+
+	fn color(&self) -> (Vec<u32>, usize) {
+		let mut colors = vec![None; index_of(self.count)];
+		let mut used = 0;
+
+		for register in 0..self.count {
+			let root = self.find(register);
+
+			if colors[index_of(root)].is_some() {
+				continue;
+			}
+
+			let taken: HashSet<u32> = self.interference[index_of(root)]
+				.iter()
+				.filter_map(|&register| colors[index_of(self.find(register))])
+				.collect();
+
+			let color = (0..).find(|color| !taken.contains(color)).unwrap();
+
+			colors[index_of(root)] = Some(color);
+			used = used.max(index_of(color) + 1);
+		}
+
+		let colors = (0..self.count)
+			.map(|register| colors[index_of(self.find(register))].unwrap())
+			.collect();
+
+		(colors, used)
+	}
+}
+
+impl Allocator for Coalescing {
+	fn reset<I>(&mut self, nodes: &Nodes, _roots: I, _topological: &mut ReverseTopological)
+	where
+		I: IntoIterator<Item = Id>,
+	{
+		self.assigned.reset(nodes);
+		self.count = 0;
+	}
+
+	fn assigned(&self) -> &ResultMap {
+		&self.assigned
+	}
+
+	fn register_count(&self) -> usize {
+		index_of(self.count)
+	}
+
+	fn fetch(&mut self, link: Link) -> u32 {
+		self.assigned.get(link)
+	}
+
+	fn reuse(&mut self, _nodes: &Nodes, link: Link, register: u32) {
+		self.assigned.set(link, register);
+	}
+
+	fn reserve(&mut self, _nodes: &Nodes, link: Link) -> u32 {
+		let register = self.count;
+
+		self.count += 1;
+		self.assigned.set(link, register);
+
+		register
+	}
+
+	// Sharing is left to coalescing, which sees the whole program.
+	fn reuse_or_reserve(&mut self, nodes: &Nodes, link: Link, _preferred: u32) -> u32 {
+		self.reserve(nodes, link)
+	}
+
+	fn finish(&mut self, program: &mut Program) {
+		let count = index_of(self.count);
+
+		self.interference.clear();
+		self.interference.resize_with(count, HashSet::new);
+		self.parents.clear();
+		self.parents.extend(0..self.count);
+
+		if !program.bodies().is_empty() {
+			self.live_body(program.bodies(), 0, &mut vec![false; count], true);
+		}
+
+		self.coalesce(program.bodies());
+
+		let (colors, used) = self.color();
+
+		for body in program.bodies_mut() {
+			let list = std::mem::take(body).into_vec();
+
+			*body = list
+				.into_iter()
+				.filter_map(|mut insn| {
+					insn.reads_mut()
+						.into_iter()
+						.for_each(|register| *register = colors[index_of(*register)]);
+
+					if let Some(register) = insn.writes_mut() {
+						*register = colors[index_of(*register)];
+					}
+
+					match insn {
+						Instruction::Move { from, to } if from == to => None,
+						insn => Some(insn),
+					}
+				})
+				.collect();
+		}
+
+		program.set_locals(used);
+	}
+}
@@ -0,0 +1,180 @@
+use std::collections::{HashMap, HashSet};
+
+use regioned::{
+	data_flow::{
+		link::{Id, Link},
+		node::Parameters,
+	},
+	visit::reverse_topological::ReverseTopological,
+};
+
+use crate::hir::data::{Node, Nodes, Simple};
+
+/// Orders nodes so that fewer values are live at once, which keeps register
+/// counts down. Each region is list scheduled from its end upwards, picking
+/// the ready node that frees the most values and preferring loads so they sit
+/// right before their uses. The order otherwise follows the same rules as
+/// `ReverseTopological`, with regions emitted right before their compound.
+#[derive(Default)]
+pub struct Scheduler {
+	seen: HashSet<Id>,
+	ranks: HashMap<Id, usize>,
+	order: Vec<Id>,
+}
+
+type Value = (Id, usize);
+
+// The live ports of each node, so that the values a node frees are found
+// without scanning every live value.
+type Live = HashMap<Id, HashSet<usize>>;
+
+fn value_of(link: Link) -> Value {
+	(link.node, usize::from(link.port))
+}
+
+fn is_live(live: &Live, (node, port): Value) -> bool {
+	live.get(&node).is_some_and(|ports| ports.contains(&port))
+}
+
+fn dependencies_of(nodes: &Nodes, id: Id) -> Vec<Id> {
+	let mut list: Vec<_> = nodes[id].parameters().map(|link| link.node).collect();
+
+	list.sort_unstable();
+	list.dedup();
+	list
+}
+
+impl Scheduler {
+	#[must_use]
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	fn find_members<I>(&mut self, nodes: &Nodes, sinks: I) -> Vec<Id>
+	where
+		I: IntoIterator<Item = Id>,
+	{
+		let mut stack: Vec<_> = sinks.into_iter().collect();
+		let mut members = Vec::new();
+
+		while let Some(id) = stack.pop() {
+			if !self.seen.insert(id) {
+				continue;
+			}
+
+			members.push(id);
+			stack.extend(nodes[id].parameters().map(|link| link.node));
+		}
+
+		members
+	}
+
+	fn pick(&self, nodes: &Nodes, ready: &[Id], live: &Live) -> usize {
+		let key = |id: Id| {
+			let freed = live.get(&id).map_or(0, HashSet::len);
+			let added = nodes[id]
+				.parameters()
+				.map(|&link| value_of(link))
+				.filter(|&value| !is_live(live, value))
+				.collect::<HashSet<_>>()
+				.len();
+
+			let gain = i64::try_from(freed).unwrap() - i64::try_from(added).unwrap();
+			let is_load = matches!(nodes[id], Node::Simple(Simple::Load { .. }));
+
+			(gain, is_load, self.ranks[&id])
+		};
+
+		(0..ready.len())
+			.max_by_key(|&index| key(ready[index]))
+			.unwrap()
+	}
+
+	// Works from the sinks upwards, so a node becomes ready once all of its
+	// users are placed. The `Start` of a region always goes first.
+	fn schedule(&self, nodes: &Nodes, members: &[Id], start: Option<Id>) -> Vec<Id> {
+		let mut users: HashMap<Id, usize> = members.iter().map(|&id| (id, 0)).collect();
+
+		for &id in members {
+			for dependency in dependencies_of(nodes, id) {
+				*users.get_mut(&dependency).unwrap() += 1;
+			}
+		}
+
+		let mut ready: Vec<_> = members
+			.iter()
+			.copied()
+			.filter(|&id| users[&id] == 0 && Some(id) != start)
+			.collect();
+
+		let mut live = Live::new();
+		let mut list = Vec::with_capacity(members.len());
+
+		while !ready.is_empty() {
+			let id = ready.swap_remove(self.pick(nodes, &ready, &live));
+
+			live.remove(&id);
+
+			for &link in nodes[id].parameters() {
+				let (node, port) = value_of(link);
+
+				live.entry(node).or_default().insert(port);
+			}
+
+			list.push(id);
+
+			for dependency in dependencies_of(nodes, id) {
+				let count = users.get_mut(&dependency).unwrap();
+
+				*count -= 1;
+
+				if *count == 0 && Some(dependency) != start {
+					ready.push(dependency);
+				}
+			}
+		}
+
+		list.extend(start);
+		list.reverse();
+		list
+	}
+
+	fn emit<I>(&mut self, nodes: &Nodes, sinks: I, start: Option<Id>)
+	where
+		I: IntoIterator<Item = Id>,
+	{
+		let members = self.find_members(nodes, sinks);
+
+		for id in self.schedule(nodes, &members, start) {
+			if let Node::Compound(compound) = &nodes[id] {
+				for region in compound.regions() {
+					self.emit(nodes, [region.end(), region.start()], Some(region.start()));
+				}
+			}
+
+			self.order.push(id);
+		}
+	}
+
+	/// Returns every node reachable from the roots in the order they should
+	/// be sequenced in.
+	pub fn run<I>(&mut self, nodes: &Nodes, roots: I, topological: &mut ReverseTopological) -> &[Id]
+	where
+		I: IntoIterator<Item = Id> + Clone,
+	{
+		self.ranks.clear();
+		self.ranks.extend(
+			topological
+				.iter(nodes, roots.clone())
+				.enumerate()
+				.map(|(rank, id)| (id, rank)),
+		);
+
+		self.seen.clear();
+		self.order.clear();
+
+		self.emit(nodes, roots, None);
+
+		&self.order
+	}
+}
@@ -10,21 +10,29 @@ use regioned::{
 
 use crate::hir::{
 	data::{Node, Nodes, Simple},
+	kind::Kinds,
 	parser::ParseData,
 };
 
 use super::{
 	data::{Instruction, Program},
-	registers::Registers,
+	registers::{Allocator, Registers},
+	scheduler::Scheduler,
 };
 
 #[derive(Default)]
-pub struct Sequencer {
+pub struct Sequencer<A = Registers> {
 	parents: HashMap<Id, Id>,
 	regions: Vec<usize>,
 	bodies: Vec<Vec<Instruction>>,
 
-	registers: Registers,
+	kinds: Kinds,
+	states: bool,
+
+	scheduler: Scheduler,
+	scheduling: bool,
+
+	registers: A,
 }
 
 impl Sequencer {
@@ -32,6 +40,58 @@ impl Sequencer {
 	pub fn new() -> Self {
 		Self::default()
 	}
+}
+
+impl<A: Allocator> Sequencer<A> {
+	/// Creates a sequencer that hands out registers with the given allocator.
+	#[must_use]
+	pub fn with_allocator(registers: A) -> Self {
+		Self {
+			parents: HashMap::new(),
+			regions: Vec::new(),
+			bodies: Vec::new(),
+			kinds: Kinds::new(),
+			states: false,
+			scheduler: Scheduler::new(),
+			scheduling: false,
+			registers,
+		}
+	}
+
+	/// Sets whether memory and IO states are kept in registers. When they are
+	/// not, state edges only order the instructions and every state operand
+	/// is left as `None`.
+	#[must_use]
+	pub fn with_states(mut self, states: bool) -> Self {
+		self.states = states;
+		self
+	}
+
+	/// Sets whether nodes are reordered to keep fewer values live at once,
+	/// instead of being sequenced in plain topological order.
+	#[must_use]
+	pub fn with_scheduling(mut self, scheduling: bool) -> Self {
+		self.scheduling = scheduling;
+		self
+	}
+
+	fn is_dropped(&self, link: Link) -> bool {
+		!self.states && self.kinds.get(link).is_state()
+	}
+
+	fn fetch_state(&mut self, link: Link) -> Option<u32> {
+		(!self.is_dropped(link)).then(|| self.registers.fetch(link))
+	}
+
+	// The state result is either the same register as the state operand or
+	// a new one, in which case the state has to be moved there.
+	fn pass_state(&mut self, nodes: &Nodes, link: Link, state: Option<u32>) {
+		if let Some(state) = state {
+			let post = self.registers.reuse_or_reserve(nodes, link, state);
+
+			self.try_add_move(state, post);
+		}
+	}
 
 	fn reset<I>(&mut self, nodes: &Nodes, roots: I, topological: &mut ReverseTopological)
 	where
@@ -65,6 +125,7 @@ impl Sequencer {
 
 		match *simple {
 			Simple::NoOp => {}
+			Simple::Merge { .. } | Simple::Memory | Simple::IO if self.is_dropped(first) => {}
 			Simple::Merge { ref states } => {
 				let iter = states.iter().rev().map(|link| self.registers.fetch(*link));
 
@@ -103,13 +164,13 @@ impl Sequencer {
 				self.add(Instruction::Sub { result, lhs, rhs });
 			}
 			Simple::Load { state, pointer } => {
-				let state = self.registers.fetch(state);
-				let post = self.registers.reuse_or_reserve(nodes, first, state);
+				let state = self.fetch_state(state);
+
+				self.pass_state(nodes, first, state);
+
 				let pointer = self.registers.fetch(pointer);
 				let result = self.registers.reserve(nodes, results.next().unwrap());
 
-				self.try_add_move(state, post);
-
 				self.add(Instruction::Load {
 					result,
 					pointer,
@@ -121,7 +182,7 @@ impl Sequencer {
 				pointer,
 				value,
 			} => {
-				let state = self.registers.fetch(state);
+				let state = self.fetch_state(state);
 				let pointer = self.registers.fetch(pointer);
 				let value = self.registers.fetch(value);
 
@@ -131,28 +192,24 @@ impl Sequencer {
 					state,
 				});
 
-				let post = self.registers.reuse_or_reserve(nodes, first, state);
-
-				self.try_add_move(state, post);
+				self.pass_state(nodes, first, state);
 			}
 			Simple::Ask { state } => {
-				let state = self.registers.fetch(state);
-				let post = self.registers.reuse_or_reserve(nodes, first, state);
-				let result = self.registers.reserve(nodes, results.next().unwrap());
+				let state = self.fetch_state(state);
+
+				self.pass_state(nodes, first, state);
 
-				self.try_add_move(state, post);
+				let result = self.registers.reserve(nodes, results.next().unwrap());
 
 				self.add(Instruction::Ask { result, state });
 			}
 			Simple::Tell { state, value } => {
-				let state = self.registers.fetch(state);
+				let state = self.fetch_state(state);
 				let value = self.registers.fetch(value);
 
 				self.add(Instruction::Tell { value, state });
 
-				let post = self.registers.reuse_or_reserve(nodes, first, state);
-
-				self.try_add_move(state, post);
+				self.pass_state(nodes, first, state);
 			}
 		}
 	}
@@ -168,19 +225,25 @@ impl Sequencer {
 				if regions[0].start() == id {
 					// Discard all predecessor references.
 					for link in parameters {
-						self.registers.fetch(*link);
+						self.fetch_state(*link);
 					}
 
 					let results = nodes[regions[0].end()].parameters().len();
 
 					// Reserve as many registers as there are results.
 					for link in Link::from(parent).iter().take(results) {
-						self.registers.reserve(nodes, link);
+						if !self.is_dropped(link) {
+							self.registers.reserve(nodes, link);
+						}
 					}
 				}
 
 				// Reuse input registers directly for each region.
 				Link::from(id).iter().zip(parameters).for_each(|entry| {
+					if self.is_dropped(*entry.1) {
+						return;
+					}
+
 					let predecessor = self.registers.assigned().get(*entry.1);
 
 					self.registers.reuse(nodes, entry.0, predecessor);
@@ -195,6 +258,10 @@ impl Sequencer {
 				// Discard predecessor, reserve matching result, reuse the register as input,
 				// and move the input there.
 				iter.for_each(|(entry, end)| {
+					if self.is_dropped(*entry.1) {
+						return;
+					}
+
 					let from = self.registers.fetch(*entry.1);
 					let to = self.registers.reuse_or_reserve(nodes, entry.0, from);
 
@@ -219,6 +286,10 @@ impl Sequencer {
 		}
 
 		for (to, from) in Link::from(parent).iter().zip(parameters) {
+			if self.is_dropped(to) {
+				continue;
+			}
+
 			let to = self.registers.assigned().get(to);
 			let from = self.registers.fetch(*from);
 
@@ -289,9 +360,19 @@ impl Sequencer {
 
 		self.find_parents(nodes);
 
+		if !self.states {
+			self.kinds.run(nodes, roots, topological);
+		}
+
 		self.reset(nodes, roots, topological);
 
-		for id in topological.iter(nodes, roots) {
+		let order: Vec<_> = if self.scheduling {
+			self.scheduler.run(nodes, roots, topological).to_vec()
+		} else {
+			topological.iter(nodes, roots).collect()
+		};
+
+		for id in order {
 			match &nodes[id] {
 				Node::Simple(simple) => self.add_simple(simple, nodes, id),
 				Node::Marker(marker) => self.add_marker(marker, nodes, id),
@@ -305,7 +386,10 @@ impl Sequencer {
 			.collect();
 
 		let locals = self.registers.register_count();
+		let mut program = Program::new(bodies, locals);
+
+		self.registers.finish(&mut program);
 
-		Program::new(bodies, locals)
+		program
 	}
 }
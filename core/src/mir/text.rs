@@ -0,0 +1,420 @@
+//! A stable text syntax for programs, with nested bodies written inline.
+//! State operands come first and are left out when states were not retained.
+//!
+//! ```text
+//! locals 3
+//! r0 = memory
+//! r1 = integer 0
+//! r2 = load r0, r1
+//! select r2 {
+//! } {
+//!     repeat {
+//!         r2 = load r0, r1
+//!     } while r2
+//! }
+//! ```
+
+use std::io::{self, Write};
+
+use super::data::{Instruction, Program};
+
+fn write_indent(w: &mut dyn Write, depth: usize) -> io::Result<()> {
+	(0..depth).try_for_each(|_| write!(w, "\t"))
+}
+
+fn write_state(w: &mut dyn Write, state: Option<u32>) -> io::Result<()> {
+	match state {
+		Some(state) => write!(w, "r{state}, "),
+		None => Ok(()),
+	}
+}
+
+fn write_insn(
+	w: &mut dyn Write,
+	depth: usize,
+	bodies: &[Box<[Instruction]>],
+	insn: &Instruction,
+) -> io::Result<()> {
+	match insn {
+		Instruction::Memory { result } => writeln!(w, "r{result} = memory"),
+		Instruction::IO { result } => writeln!(w, "r{result} = io"),
+		Instruction::Integer { result, value } => writeln!(w, "r{result} = integer {value}"),
+		Instruction::Move { from, to } => writeln!(w, "r{to} = move r{from}"),
+		Instruction::Add { result, lhs, rhs } => writeln!(w, "r{result} = add r{lhs}, r{rhs}"),
+		Instruction::Sub { result, lhs, rhs } => writeln!(w, "r{result} = sub r{lhs}, r{rhs}"),
+		Instruction::Load {
+			result,
+			pointer,
+			state,
+		} => {
+			write!(w, "r{result} = load ")?;
+			write_state(w, *state)?;
+			writeln!(w, "r{pointer}")
+		}
+		Instruction::Store {
+			pointer,
+			value,
+			state,
+		} => {
+			write!(w, "store ")?;
+			write_state(w, *state)?;
+			writeln!(w, "r{pointer}, r{value}")
+		}
+		Instruction::Ask { result, state } => {
+			write!(w, "r{result} = ask")?;
+
+			match state {
+				Some(state) => writeln!(w, " r{state}"),
+				None => writeln!(w),
+			}
+		}
+		Instruction::Tell { value, state } => {
+			write!(w, "tell ")?;
+			write_state(w, *state)?;
+			writeln!(w, "r{value}")
+		}
+		Instruction::Select { condition, code } => {
+			writeln!(w, "select r{condition} {{")?;
+
+			for (i, code) in code.iter().enumerate() {
+				if i != 0 {
+					write_indent(w, depth)?;
+					writeln!(w, "}} {{")?;
+				}
+
+				write_block(w, depth + 1, bodies, *code)?;
+			}
+
+			write_indent(w, depth)?;
+			writeln!(w, "}}")
+		}
+		Instruction::Repeat { code, condition } => {
+			writeln!(w, "repeat {{")?;
+
+			write_block(w, depth + 1, bodies, *code)?;
+
+			write_indent(w, depth)?;
+			writeln!(w, "}} while r{condition}")
+		}
+	}
+}
+
+fn write_block(
+	w: &mut dyn Write,
+	depth: usize,
+	bodies: &[Box<[Instruction]>],
+	index: usize,
+) -> io::Result<()> {
+	bodies[index].iter().try_for_each(|insn| {
+		write_indent(w, depth)?;
+
+		write_insn(w, depth, bodies, insn)
+	})
+}
+
+/// # Errors
+///
+/// Returns an error if the writer fails.
+pub fn write(writer: &mut dyn Write, program: &Program) -> io::Result<()> {
+	writeln!(writer, "locals {}", program.locals())?;
+
+	write_block(writer, 0, program.bodies(), 0)
+}
+
+#[derive(Debug)]
+pub enum ReadError {
+	UnexpectedToken { line: usize },
+	UnbalancedBraces { line: usize },
+}
+
+enum Frame {
+	Select { condition: u32, code: Vec<usize> },
+	Repeat { code: usize },
+}
+
+#[derive(Default)]
+pub struct Reader {
+	bodies: Vec<Vec<Instruction>>,
+	frames: Vec<Frame>,
+	current: Vec<usize>,
+	locals: usize,
+	line: usize,
+}
+
+impl Reader {
+	#[must_use]
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	const fn unexpected(&self) -> ReadError {
+		ReadError::UnexpectedToken { line: self.line }
+	}
+
+	const fn unbalanced(&self) -> ReadError {
+		ReadError::UnbalancedBraces { line: self.line }
+	}
+
+	fn read_register(&mut self, token: &str) -> Result<u32, ReadError> {
+		let register: u32 = token
+			.strip_prefix('r')
+			.and_then(|number| number.parse().ok())
+			.ok_or_else(|| self.unexpected())?;
+
+		self.locals = self.locals.max(usize::try_from(register).unwrap() + 1);
+
+		Ok(register)
+	}
+
+	fn read_state(&mut self, tokens: &[&str]) -> Result<Option<u32>, ReadError> {
+		match *tokens {
+			[] => Ok(None),
+			[state] => self.read_register(state).map(Some),
+			_ => Err(self.unexpected()),
+		}
+	}
+
+	fn add(&mut self, instruction: Instruction) {
+		let index = self.current.last().unwrap();
+
+		self.bodies[*index].push(instruction);
+	}
+
+	fn open_body(&mut self) -> usize {
+		let index = self.bodies.len();
+
+		self.bodies.push(Vec::new());
+		self.current.push(index);
+
+		index
+	}
+
+	fn open_select(&mut self, condition: &str) -> Result<(), ReadError> {
+		let condition = self.read_register(condition)?;
+		let code = vec![self.open_body()];
+
+		self.frames.push(Frame::Select { condition, code });
+
+		Ok(())
+	}
+
+	fn open_repeat(&mut self) {
+		let code = self.open_body();
+
+		self.frames.push(Frame::Repeat { code });
+	}
+
+	fn open_next(&mut self) -> Result<(), ReadError> {
+		if !matches!(self.frames.last(), Some(Frame::Select { .. })) {
+			return Err(self.unbalanced());
+		}
+
+		self.current.pop();
+
+		let index = self.open_body();
+
+		if let Some(Frame::Select { code, .. }) = self.frames.last_mut() {
+			code.push(index);
+		}
+
+		Ok(())
+	}
+
+	fn close_select(&mut self) -> Result<(), ReadError> {
+		let Some(Frame::Select { condition, code }) = self.frames.pop() else {
+			return Err(self.unbalanced());
+		};
+
+		self.current.pop();
+		self.add(Instruction::Select {
+			condition,
+			code: code.into(),
+		});
+
+		Ok(())
+	}
+
+	fn close_repeat(&mut self, condition: &str) -> Result<(), ReadError> {
+		let Some(Frame::Repeat { code }) = self.frames.pop() else {
+			return Err(self.unbalanced());
+		};
+
+		let condition = self.read_register(condition)?;
+
+		self.current.pop();
+		self.add(Instruction::Repeat { code, condition });
+
+		Ok(())
+	}
+
+	fn read_operation(
+		&mut self,
+		result: &str,
+		operation: &str,
+		arguments: &[&str],
+	) -> Result<Instruction, ReadError> {
+		let result = self.read_register(result)?;
+
+		let instruction = match (operation, arguments) {
+			("memory", []) => Instruction::Memory { result },
+			("io", []) => Instruction::IO { result },
+			("integer", [value]) => {
+				let value = value.parse().map_err(|_| self.unexpected())?;
+
+				Instruction::Integer { result, value }
+			}
+			("move", [from]) => Instruction::Move {
+				from: self.read_register(from)?,
+				to: result,
+			},
+			("add", [lhs, rhs]) => Instruction::Add {
+				result,
+				lhs: self.read_register(lhs)?,
+				rhs: self.read_register(rhs)?,
+			},
+			("sub", [lhs, rhs]) => Instruction::Sub {
+				result,
+				lhs: self.read_register(lhs)?,
+				rhs: self.read_register(rhs)?,
+			},
+			("load", [state @ .., pointer]) => Instruction::Load {
+				result,
+				pointer: self.read_register(pointer)?,
+				state: self.read_state(state)?,
+			},
+			("ask", state) => Instruction::Ask {
+				result,
+				state: self.read_state(state)?,
+			},
+			_ => return Err(self.unexpected()),
+		};
+
+		Ok(instruction)
+	}
+
+	fn read_line(&mut self, tokens: &[&str]) -> Result<(), ReadError> {
+		match *tokens {
+			[] => {}
+			["locals", count] => {
+				let count = count.parse().map_err(|_| self.unexpected())?;
+
+				self.locals = self.locals.max(count);
+			}
+			["select", condition, "{"] => self.open_select(condition)?,
+			["repeat", "{"] => self.open_repeat(),
+			["}", "{"] => self.open_next()?,
+			["}"] => self.close_select()?,
+			["}", "while", condition] => self.close_repeat(condition)?,
+			["store", ref state @ .., pointer, value] => {
+				let instruction = Instruction::Store {
+					pointer: self.read_register(pointer)?,
+					value: self.read_register(value)?,
+					state: self.read_state(state)?,
+				};
+
+				self.add(instruction);
+			}
+			["tell", ref state @ .., value] => {
+				let instruction = Instruction::Tell {
+					value: self.read_register(value)?,
+					state: self.read_state(state)?,
+				};
+
+				self.add(instruction);
+			}
+			[result, "=", operation, ref arguments @ ..] => {
+				let instruction = self.read_operation(result, operation, arguments)?;
+
+				self.add(instruction);
+			}
+			_ => return Err(self.unexpected()),
+		}
+
+		Ok(())
+	}
+
+	/// # Errors
+	///
+	/// Returns `ReadError::UnexpectedToken` if a line is malformed.
+	/// Returns `ReadError::UnbalancedBraces` if bodies are not opened and closed properly.
+	pub fn read(&mut self, source: &str) -> Result<Program, ReadError> {
+		self.bodies.clear();
+		self.frames.clear();
+		self.current.clear();
+		self.locals = 0;
+
+		self.open_body();
+
+		for (index, line) in source.lines().enumerate() {
+			let line = line.replace(',', " ");
+			let tokens: Vec<_> = line.split_whitespace().collect();
+
+			self.line = index + 1;
+			self.read_line(&tokens)?;
+		}
+
+		if !self.frames.is_empty() {
+			return Err(self.unbalanced());
+		}
+
+		let bodies = std::mem::take(&mut self.bodies)
+			.into_iter()
+			.map(|body| body.into_iter().collect())
+			.collect();
+
+		Ok(Program::new(bodies, self.locals))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use regioned::visit::reverse_topological::ReverseTopological;
+
+	use crate::{hir::parser::Parser, mir::sequencer::Sequencer};
+
+	use super::{write, Program, Reader};
+
+	const EXAMPLE: &str = "locals 3
+r0 = memory
+r1 = integer 0
+r2 = load r0, r1
+select r2 {
+} {
+	repeat {
+		r2 = load r0, r1
+	} while r2
+}
+";
+
+	fn print(program: &Program) -> String {
+		let mut output = Vec::new();
+
+		write(&mut output, program).unwrap();
+
+		String::from_utf8(output).unwrap()
+	}
+
+	#[test]
+	fn reads_example() {
+		let program = Reader::new().read(EXAMPLE).unwrap();
+
+		assert_eq!(print(&program), EXAMPLE);
+	}
+
+	#[test]
+	fn round_trip() {
+		for code in ["", "+>-<.,", "+[->+<]>.", ",[.,]", "+[[-]>[<]],[>+<-]"] {
+			let data = Parser::new().parse(code.char_indices()).unwrap();
+
+			for states in [false, true] {
+				let program = Sequencer::new()
+					.with_states(states)
+					.sequence(&data, &mut ReverseTopological::new());
+				let text = print(&program);
+				let read = Reader::new().read(&text).unwrap();
+
+				assert_eq!(print(&read), text, "`{code}`");
+			}
+		}
+	}
+}
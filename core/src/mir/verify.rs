@@ -0,0 +1,301 @@
+use super::data::{Instruction, Program};
+
+#[derive(Debug)]
+pub enum VerifyError {
+	BodyOutOfRange {
+		code: usize,
+	},
+	BodyReused {
+		code: usize,
+	},
+	BodyUnused {
+		code: usize,
+	},
+	EmptySelect {
+		code: usize,
+		position: usize,
+	},
+	RegisterOutOfRange {
+		register: u32,
+	},
+	Uninitialized {
+		code: usize,
+		position: usize,
+		register: u32,
+	},
+}
+
+/// Checks that every register is written before it is read on all paths,
+/// and that bodies form a tree rooted at the entry body.
+#[derive(Default)]
+pub struct Verifier {
+	visited: Vec<bool>,
+	locals: usize,
+}
+
+impl Verifier {
+	#[must_use]
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	fn enter(&mut self, code: usize) -> Result<(), VerifyError> {
+		let visited = self
+			.visited
+			.get_mut(code)
+			.ok_or(VerifyError::BodyOutOfRange { code })?;
+
+		if std::mem::replace(visited, true) {
+			return Err(VerifyError::BodyReused { code });
+		}
+
+		Ok(())
+	}
+
+	fn check_range(&self, register: u32) -> Result<usize, VerifyError> {
+		usize::try_from(register)
+			.ok()
+			.filter(|&index| index < self.locals)
+			.ok_or(VerifyError::RegisterOutOfRange { register })
+	}
+
+	fn check_read(
+		&self,
+		defined: &[bool],
+		code: usize,
+		position: usize,
+		register: u32,
+	) -> Result<(), VerifyError> {
+		let index = self.check_range(register)?;
+
+		if defined[index] {
+			Ok(())
+		} else {
+			Err(VerifyError::Uninitialized {
+				code,
+				position,
+				register,
+			})
+		}
+	}
+
+	fn check_select(
+		&mut self,
+		bodies: &[Box<[Instruction]>],
+		defined: &mut [bool],
+		list: &[usize],
+	) -> Result<(), VerifyError> {
+		let mut joined: Option<Vec<bool>> = None;
+
+		for &code in list {
+			let mut inner = defined.to_vec();
+
+			self.check_body(bodies, &mut inner, code)?;
+
+			match &mut joined {
+				Some(joined) => joined.iter_mut().zip(inner).for_each(|(a, b)| *a &= b),
+				None => joined = Some(inner),
+			}
+		}
+
+		if let Some(joined) = joined {
+			defined.copy_from_slice(&joined);
+		}
+
+		Ok(())
+	}
+
+	fn check_insn(
+		&mut self,
+		bodies: &[Box<[Instruction]>],
+		defined: &mut [bool],
+		code: usize,
+		position: usize,
+	) -> Result<(), VerifyError> {
+		let insn = &bodies[code][position];
+
+		match insn {
+			Instruction::Select {
+				condition,
+				code: list,
+			} => {
+				if list.is_empty() {
+					return Err(VerifyError::EmptySelect { code, position });
+				}
+
+				self.check_read(defined, code, position, *condition)?;
+				self.check_select(bodies, defined, list)?;
+			}
+			// Later iterations only ever see more registers defined than the
+			// first, so checking the first iteration is enough.
+			Instruction::Repeat {
+				code: inner,
+				condition,
+			} => {
+				self.check_body(bodies, defined, *inner)?;
+				self.check_read(defined, code, position, *condition)?;
+			}
+			_ => {
+				for register in insn.reads() {
+					self.check_read(defined, code, position, register)?;
+				}
+			}
+		}
+
+		if let Some(register) = insn.writes() {
+			let index = self.check_range(register)?;
+
+			defined[index] = true;
+		}
+
+		Ok(())
+	}
+
+	fn check_body(
+		&mut self,
+		bodies: &[Box<[Instruction]>],
+		defined: &mut [bool],
+		code: usize,
+	) -> Result<(), VerifyError> {
+		self.enter(code)?;
+
+		(0..bodies[code].len())
+			.try_for_each(|position| self.check_insn(bodies, defined, code, position))
+	}
+
+	/// # Errors
+	///
+	/// Returns `VerifyError::BodyOutOfRange` if an instruction refers to a body that does not exist.
+	/// Returns `VerifyError::BodyReused` if a body is referenced more than once, or at all if it is the entry.
+	/// Returns `VerifyError::BodyUnused` if a body is never referenced.
+	/// Returns `VerifyError::EmptySelect` if a `Select` has no bodies to choose from.
+	/// Returns `VerifyError::RegisterOutOfRange` if a register is not covered by the locals.
+	/// Returns `VerifyError::Uninitialized` if a register may be read before it is written.
+	pub fn run(&mut self, program: &Program) -> Result<(), VerifyError> {
+		let bodies = program.bodies();
+		let mut defined = vec![false; program.locals()];
+
+		self.visited.clear();
+		self.visited.resize(bodies.len(), false);
+		self.locals = program.locals();
+
+		self.check_body(bodies, &mut defined, 0)?;
+
+		match self.visited.iter().position(|&visited| !visited) {
+			Some(code) => Err(VerifyError::BodyUnused { code }),
+			None => Ok(()),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::mir::{
+		data::{Instruction, Program},
+		text::Reader,
+	};
+
+	use super::{Verifier, VerifyError};
+
+	const SELECT: &str = "r0 = integer 0\nselect r0 {\n} {\n}\n";
+
+	fn read(text: &str) -> Program {
+		Reader::new().read(text).unwrap()
+	}
+
+	fn verify(program: &Program) -> Result<(), VerifyError> {
+		Verifier::new().run(program)
+	}
+
+	// The reader always gives a `Select` the bodies it lists, so the shapes
+	// it cannot express are made by editing the one at the end of the entry.
+	fn with_select_code(code: &[usize]) -> Program {
+		let mut program = read(SELECT);
+		let Some(Instruction::Select { code: list, .. }) = program.bodies_mut()[0].last_mut()
+		else {
+			unreachable!()
+		};
+
+		*list = code.into();
+
+		program
+	}
+
+	#[test]
+	fn accepts_nested() {
+		let program = read(
+			"r0 = memory\nr1 = integer 0\nrepeat {\nr2 = load r0, r1\nselect r2 {\nr3 = move r2\n} {\nr3 = integer 1\n}\nstore r0, r1, r3\n} while r2\n",
+		);
+
+		assert!(verify(&program).is_ok());
+	}
+
+	#[test]
+	fn reports_body_out_of_range() {
+		let program = with_select_code(&[1, 9]);
+
+		assert!(matches!(
+			verify(&program),
+			Err(VerifyError::BodyOutOfRange { code: 9 })
+		));
+	}
+
+	#[test]
+	fn reports_body_reused() {
+		let program = with_select_code(&[1, 1]);
+
+		assert!(matches!(
+			verify(&program),
+			Err(VerifyError::BodyReused { code: 1 })
+		));
+	}
+
+	#[test]
+	fn reports_body_unused() {
+		let program = with_select_code(&[1]);
+
+		assert!(matches!(
+			verify(&program),
+			Err(VerifyError::BodyUnused { code: 2 })
+		));
+	}
+
+	#[test]
+	fn reports_empty_select() {
+		let program = with_select_code(&[]);
+
+		assert!(matches!(
+			verify(&program),
+			Err(VerifyError::EmptySelect {
+				code: 0,
+				position: 1
+			})
+		));
+	}
+
+	#[test]
+	fn reports_register_out_of_range() {
+		let mut program = read("r0 = integer 0\nr1 = move r0\n");
+
+		program.set_locals(1);
+
+		assert!(matches!(
+			verify(&program),
+			Err(VerifyError::RegisterOutOfRange { register: 1 })
+		));
+	}
+
+	#[test]
+	fn reports_uninitialized() {
+		let program = read("r0 = integer 0\nselect r0 {\nr1 = integer 1\n} {\n}\ntell r1\n");
+
+		assert!(matches!(
+			verify(&program),
+			Err(VerifyError::Uninitialized {
+				code: 0,
+				position: 2,
+				register: 1
+			})
+		));
+	}
+}
@@ -0,0 +1,157 @@
+//! Compiles `BrainFxck` programs to Rust at compile time. The macro is used
+//! as `telepathy::bf!` with the `macros` feature of `telepathy` enabled.
+//!
+//! ```
+//! let program = telepathy::bf!("++++++++[>++++++++<-]>+.");
+//! let mut output = Vec::new();
+//!
+//! program(&mut std::io::empty(), &mut output)?;
+//!
+//! assert_eq!(output, b"A");
+//! # Ok::<(), std::io::Error>(())
+//! ```
+//!
+//! The source can also be read from a file relative to the crate root with
+//! `bf!(include = "program.bf")`. The macro expands to a function of type
+//! `fn(&mut dyn Read, &mut dyn Write) -> std::io::Result<()>`.
+//!
+//! Malformed programs are rejected at compile time. Pointing at the bracket
+//! at fault inside the literal needs a nightly compiler, as stable Rust does
+//! not give spans within a literal, so on stable the error points at the
+//! whole literal instead.
+//!
+//! ```compile_fail
+//! let program = telepathy::bf!("+[-]]");
+//! ```
+
+use std::path::PathBuf;
+
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use regioned::visit::reverse_topological::ReverseTopological;
+use syn::{
+	parse::{Parse, ParseStream},
+	parse_macro_input, Ident, LitStr, Token,
+};
+use telepathy_core::{
+	codegen,
+	hir::{
+		optimizer::Optimizer,
+		parser::{ParseError, Parser},
+	},
+	mir::{peephole::Peephole, registers::Coalescing, sequencer::Sequencer},
+};
+
+enum Source {
+	Code(LitStr),
+	Include(LitStr),
+}
+
+impl Parse for Source {
+	fn parse(input: ParseStream) -> syn::Result<Self> {
+		if input.peek(LitStr) {
+			return input.parse().map(Self::Code);
+		}
+
+		let name: Ident = input.parse()?;
+
+		if name != "include" {
+			return Err(syn::Error::new(
+				name.span(),
+				"expected a string or `include`",
+			));
+		}
+
+		input.parse::<Token![=]>()?;
+		input.parse().map(Self::Include)
+	}
+}
+
+// Points into the literal when it has no escapes and the compiler is nightly,
+// as `subspan` always returns `None` on stable. Otherwise points at the whole
+// literal.
+fn span_of(literal: &LitStr, code: &str, start: usize) -> Span {
+	let token = literal.token();
+	let is_plain = token.to_string().len() == code.len() + 2;
+
+	is_plain
+		.then(|| token.subspan(start + 1..start + 2))
+		.flatten()
+		.unwrap_or_else(|| literal.span())
+}
+
+fn to_error(
+	error: &ParseError,
+	literal: &LitStr,
+	span_at: impl FnOnce(usize) -> Span,
+) -> syn::Error {
+	match *error {
+		ParseError::TooManyClosingBrackets { start } => {
+			syn::Error::new(span_at(start), "this `]` has no matching `[`")
+		}
+		ParseError::TooLittleClosingBrackets => {
+			syn::Error::new(literal.span(), "a `[` has no matching `]`")
+		}
+	}
+}
+
+fn compile(code: &str) -> Result<String, ParseError> {
+	let mut data = Parser::new().parse(code.char_indices())?;
+
+	Optimizer::with_all().run(&mut data);
+
+	let mut topological = ReverseTopological::new();
+	let mut program = Sequencer::with_allocator(Coalescing::new())
+		.with_scheduling(true)
+		.sequence(&data, &mut topological);
+
+	Peephole::new().run(&mut program);
+
+	let mut output = Vec::new();
+
+	codegen::rust::write_expression(&mut output, &program).unwrap();
+
+	Ok(String::from_utf8(output).unwrap())
+}
+
+fn expand(source: &Source) -> syn::Result<String> {
+	match source {
+		Source::Code(literal) => {
+			let code = literal.value();
+
+			compile(&code)
+				.map_err(|error| to_error(&error, literal, |start| span_of(literal, &code, start)))
+		}
+		// Errors can only point at the path, as the file is not in the source.
+		Source::Include(literal) => {
+			let root = std::env::var_os("CARGO_MANIFEST_DIR").unwrap_or_default();
+			let path = PathBuf::from(root).join(literal.value());
+			let code = std::fs::read_to_string(&path).map_err(|error| {
+				let message = format!("failed to read `{}`: {error}", path.display());
+
+				syn::Error::new(literal.span(), message)
+			})?;
+
+			let expression =
+				compile(&code).map_err(|error| to_error(&error, literal, |_| literal.span()))?;
+
+			// Depending on the file makes changes to it trigger a rebuild.
+			Ok(format!(
+				"{{ const _: &[u8] = include_bytes!({:?}); {expression} }}",
+				path.display().to_string()
+			))
+		}
+	}
+}
+
+/// Compiles a program from a string literal, or from a file with
+/// `include = "path"`, into a function that runs it.
+#[proc_macro]
+pub fn bf(input: TokenStream) -> TokenStream {
+	let source = parse_macro_input!(input as Source);
+
+	match expand(&source) {
+		Ok(expression) => expression.parse().unwrap(),
+		Err(error) => error.to_compile_error().into(),
+	}
+}
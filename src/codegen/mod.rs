@@ -1,4 +0,0 @@
-mod tab;
-
-pub mod c89;
-pub mod lua51;
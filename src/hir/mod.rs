@@ -1,3 +0,0 @@
-pub mod data;
-pub mod isle;
-pub mod parser;
@@ -1,3 +1,4 @@
-pub mod codegen;
-pub mod hir;
-pub mod mir;
+pub use telepathy_core::{codegen, hir, mir};
+
+#[cfg(feature = "macros")]
+pub use telepathy_macros::bf;
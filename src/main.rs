@@ -1,27 +1,26 @@
 use std::{
 	fs::File,
-	io::{BufWriter, Write},
+	io::{BufWriter, Error, ErrorKind, Write},
+	path::PathBuf,
 };
 
 use argh::FromArgs;
-use regioned::{
-	data_flow::link::Id,
-	dot::Dot,
-	transform::{
-		relax_dependencies::RelaxDependencies,
-		retain_only,
-		revise::{self, redo_ports, redo_ports_in_place},
-	},
-	visit::{reverse_topological::ReverseTopological, successors::Successors},
-};
+use regioned::{dot::Dot, visit::reverse_topological::ReverseTopological};
 use telepathy::{
 	codegen,
 	hir::{
-		data::{Node, Nodes, Simple},
-		isle::{self, Elided},
+		optimizer::Optimizer,
 		parser::{ParseData, Parser},
+		text::{Printer, Reader},
+		verify::Verifier,
+	},
+	mir::{
+		self,
+		data::{Instruction, Program},
+		peephole::Peephole,
+		registers::{Allocator, Coalescing, Registers},
+		sequencer::Sequencer,
 	},
-	mir::{data::Program, sequencer::Sequencer},
 };
 
 /// A `BrainFxck` optimizing compiler based on the `Regioned` implementation of
@@ -30,7 +29,10 @@ use telepathy::{
 #[derive(FromArgs)]
 struct Arguments {
 	/// the target language to compile to,
-	/// currently supported: `dot`, `c`, `lua`
+	/// currently supported: `dot`, `hir`, `mir`, `stats`, `c`, `clib` for a `bf_run` function,
+	/// `lua`, `luajit`, `x86-64`, `elf`, `wat`, `wasm`, `llvm`, `qbe`, `rust`, `js`, `python`, `bf`
+	/// to regenerate the source, and `jit` to run the program right away when built with the
+	/// `jit` feature
 	#[argh(positional)]
 	target: String,
 
@@ -44,6 +46,19 @@ struct Arguments {
 	#[argh(option, short = 'o')]
 	output: Option<String>,
 
+	/// the header file to write for the `clib` target
+	/// if not specified, no header is written
+	#[argh(option)]
+	header: Option<String>,
+
+	/// whether the input is a graph in the `hir` text format
+	#[argh(switch)]
+	hir_input: bool,
+
+	/// whether the input is a program in the `mir` text format
+	#[argh(switch)]
+	mir_input: bool,
+
 	/// whether all optimizations should be performed
 	#[argh(switch, short = 'O')]
 	optimize: bool,
@@ -59,61 +74,100 @@ struct Arguments {
 	/// whether to relax dependencies of compounds
 	#[argh(switch)]
 	relax_dependencies: bool,
+
+	/// whether peephole optimizations should be performed on the `mir`
+	#[argh(switch)]
+	peephole: bool,
+
+	/// whether nodes should be scheduled to keep fewer values live
+	#[argh(switch)]
+	schedule: bool,
+
+	/// the register allocator to use,
+	/// currently supported: `simple`, `coalescing`
+	/// if not specified, `simple` is used unless optimizing
+	#[argh(option)]
+	allocator: Option<String>,
+
+	/// the directory to write numbered `dot` graphs to after each pass
+	/// if not specified, no graphs are written
+	#[argh(option)]
+	dump_passes: Option<String>,
+
+	/// whether only passes that changed the graph should be written
+	#[argh(switch)]
+	dump_changed: bool,
+
+	/// whether the graph and program should be verified after each pass
+	#[argh(switch)]
+	verify_each: bool,
+}
+
+struct Checker {
+	verifier: Option<Verifier>,
+	topological: ReverseTopological,
+}
+
+impl Checker {
+	fn new(enabled: bool) -> Self {
+		Self {
+			verifier: enabled.then(Verifier::new),
+			topological: ReverseTopological::new(),
+		}
+	}
+
+	fn check(&mut self, name: &str, data: &ParseData) {
+		let Some(verifier) = &mut self.verifier else {
+			return;
+		};
+
+		if let Err(error) = verifier.run(data.nodes(), data.roots(), &mut self.topological) {
+			panic!("graph is malformed after {name}: {error:?}");
+		}
+	}
 }
 
-fn run_fold_identity(successors: &Successors) -> impl FnMut(&mut Nodes, Id) -> Option<Node> + '_ {
-	revise::single(
-		|nodes, id| isle::identity(nodes, id.into()),
-		|nodes, id, value| {
-			redo_ports(nodes, successors, id, |port| (port == 0).then_some(value));
-
-			Simple::NoOp.into()
-		},
-	)
-}
-
-fn run_fold_expressions() -> impl FnMut(&mut Nodes, Id) -> Option<Node> {
-	revise::single(
-		|nodes, id| isle::fold(nodes, id.into()),
-		|_, _, math| Simple::from(math).into(),
-	)
-}
-
-fn run_load_store_elision(
-	successors: &Successors,
-) -> impl FnMut(&mut Nodes, Id) -> Option<Node> + '_ {
-	revise::single(
-		|nodes, id| isle::elide(nodes, id.into()),
-		|nodes, id, elided| {
-			let result = match elided {
-				Elided::Merge { state } => {
-					redo_ports_in_place(nodes, successors, id, state.node);
-
-					Simple::NoOp
-				}
-				Elided::Load { store, value } => {
-					redo_ports(nodes, successors, id, |port| match port {
-						0 => Some(store),
-						1 => Some(value),
-						_ => None,
-					});
-
-					Simple::NoOp
-				}
-				Elided::Store {
-					store,
-					pointer,
-					value,
-				} => Simple::Store {
-					state: store,
-					pointer,
-					value,
-				},
-			};
-
-			result.into()
-		},
-	)
+struct Dumper {
+	dot: Dot,
+	directory: Option<PathBuf>,
+	changed_only: bool,
+	count: usize,
+}
+
+impl Dumper {
+	fn new(directory: Option<&str>, changed_only: bool) -> Self {
+		let directory = directory.map(|name| {
+			std::fs::create_dir_all(name).expect("failed to create dump directory");
+
+			PathBuf::from(name)
+		});
+
+		Self {
+			dot: Dot::new(),
+			directory,
+			changed_only,
+			count: 0,
+		}
+	}
+
+	fn dump(&mut self, name: &str, data: &ParseData, applied: usize) {
+		let Some(directory) = &self.directory else {
+			return;
+		};
+
+		if self.changed_only && applied == 0 {
+			return;
+		}
+
+		let path = directory.join(format!("{:03}-{name}.dot", self.count));
+		let file = File::create(path).expect("failed to open dump file");
+
+		self.count += 1;
+
+		self.dot
+			.write(&mut BufWriter::new(file), data.nodes(), data.roots())
+			.expect("failed to write dump file");
+	}
 }
 
 fn load_input(name: Option<&str>) -> String {
@@ -136,75 +190,171 @@ fn load_output(name: Option<&str>) -> Box<dyn Write> {
 	}
 }
 
-fn run_optimization(
-	nodes: &mut Nodes,
-	id: Id,
-	arguments: &Arguments,
-	successors: &Successors,
-	relax: &mut RelaxDependencies,
-) -> usize {
-	let mut applied = 0;
+fn write_header(name: Option<&str>) -> std::io::Result<()> {
+	let Some(name) = name else {
+		return Ok(());
+	};
 
-	if arguments.constant_fold {
-		if run_fold_identity(successors)(nodes, id).is_some() {
-			applied += 1;
-		}
+	let mut file = BufWriter::new(File::create(name)?);
 
-		if run_fold_expressions()(nodes, id).is_some() {
-			applied += 1;
-		}
-	}
+	codegen::c89::write_header(&mut file)?;
 
-	if arguments.load_store_elide && run_load_store_elision(successors)(nodes, id).is_some() {
-		applied += 1;
-	}
+	file.flush()
+}
+
+fn set_executable(name: Option<&str>) -> std::io::Result<()> {
+	#[cfg(unix)]
+	if let Some(name) = name {
+		use std::os::unix::fs::PermissionsExt;
 
-	if arguments.relax_dependencies
-		&& applied == 0
-		&& relax.run(nodes, id, successors).unwrap_or_default() != 0
-	{
-		applied += 1;
+		std::fs::set_permissions(name, std::fs::Permissions::from_mode(0o755))?;
 	}
 
-	applied
+	#[cfg(not(unix))]
+	let _ = name;
+
+	Ok(())
+}
+
+#[cfg(feature = "jit")]
+fn run_jit(output: &mut dyn Write, program: &Program) -> std::io::Result<()> {
+	use std::io::Read;
+
+	let compiled = codegen::jit::compile(program).expect("failed to compile program");
+	let mut input = std::io::stdin().lock().bytes();
+	let mut result = Ok(());
+
+	compiled.run(&mut || input.next().and_then(Result::ok), &mut |value| {
+		if result.is_ok() {
+			result = output.write_all(&[value]);
+		}
+	});
+
+	result.and_then(|()| output.flush())
+}
+
+fn load_hir(code: &str, arguments: &Arguments) -> ParseData {
+	assert!(
+		!arguments.mir_input,
+		"target `{}` does not accept `mir` input",
+		arguments.target
+	);
+
+	if arguments.hir_input {
+		Reader::new().read(code).unwrap()
+	} else {
+		Parser::new().parse(code.char_indices()).unwrap()
+	}
 }
 
 fn process_hir(code: &str, arguments: &Arguments) -> ParseData {
-	let mut data = Parser::new().parse(code.char_indices()).unwrap();
+	let mut data = load_hir(code, arguments);
+	let mut dumper = Dumper::new(arguments.dump_passes.as_deref(), arguments.dump_changed);
+	let mut checker = Checker::new(arguments.verify_each);
 
-	let roots = data.roots();
-	let mut list = Vec::new();
-	let mut relax = RelaxDependencies::new();
-	let mut successors = Successors::new();
-	let mut topological = ReverseTopological::new();
+	checker.check("parse", &data);
+	dumper.dump("parse", &data, 1);
+
+	let mut optimizer = Optimizer::new()
+		.with_constant_fold(arguments.constant_fold)
+		.with_load_store_elide(arguments.load_store_elide)
+		.with_relax_dependencies(arguments.relax_dependencies);
+	let mut iteration = 0;
 
 	loop {
-		list.clear();
-		list.extend(topological.iter(data.nodes(), roots));
+		let applied = optimizer.iterate(&mut data);
 
-		successors.run(data.nodes(), roots, &mut topological);
+		iteration += 1;
 
-		let applied = list.iter().fold(0, |acc, &id| {
-			acc + run_optimization(data.nodes_mut(), id, arguments, &successors, &mut relax)
-		});
+		checker.check(&format!("iteration {iteration}"), &data);
+		dumper.dump(&format!("iteration-{iteration}"), &data, applied);
 
 		if applied == 0 {
 			break;
 		}
 	}
 
-	retain_only::run(data.nodes_mut(), roots, &mut topological);
+	optimizer.retain(&mut data);
+
+	checker.check("retain", &data);
+	dumper.dump("retain", &data, 1);
 
 	data
 }
 
-fn process_mir(data: &ParseData) -> Program {
+fn check_mir(name: &str, program: &Program, arguments: &Arguments) {
+	if !arguments.verify_each {
+		return;
+	}
+
+	if let Err(error) = mir::verify::Verifier::new().run(program) {
+		panic!("program is malformed after {name}: {error:?}");
+	}
+}
+
+fn sequence<A: Allocator>(
+	data: &ParseData,
+	allocator: A,
+	arguments: &Arguments,
+	states: bool,
+) -> Program {
 	let mut topological = ReverseTopological::new();
-	let mut sequencer = Sequencer::new();
+	let mut sequencer = Sequencer::with_allocator(allocator)
+		.with_states(states)
+		.with_scheduling(arguments.schedule);
 
 	sequencer.sequence(data, &mut topological)
 }
 
+fn process_mir(data: &ParseData, arguments: &Arguments, states: bool) -> Program {
+	let program = match arguments.allocator.as_deref() {
+		None | Some("simple") => sequence(data, Registers::new(), arguments, states),
+		Some("coalescing") => sequence(data, Coalescing::new(), arguments, states),
+		Some(name) => panic!("unsupported allocator `{name}`"),
+	};
+
+	check_mir("sequencing", &program, arguments);
+
+	program
+}
+
+fn load_mir(code: &str, arguments: &Arguments, states: bool) -> Program {
+	let mut program = if arguments.mir_input {
+		assert!(
+			!arguments.hir_input,
+			"`hir` and `mir` input cannot be used together"
+		);
+
+		let program = mir::text::Reader::new().read(code).unwrap();
+
+		check_mir("reading", &program, arguments);
+
+		program
+	} else {
+		process_mir(&process_hir(code, arguments), arguments, states)
+	};
+
+	if arguments.peephole {
+		Peephole::new().run(&mut program);
+
+		check_mir("peephole", &program, arguments);
+	}
+
+	program
+}
+
+fn write_stats(w: &mut dyn Write, program: &Program) -> std::io::Result<()> {
+	let instructions = program.bodies().iter().flat_map(|body| body.iter());
+	let moves = instructions
+		.clone()
+		.filter(|insn| matches!(insn, Instruction::Move { .. }))
+		.count();
+
+	writeln!(w, "locals {}", program.locals())?;
+	writeln!(w, "instructions {}", instructions.count())?;
+	writeln!(w, "moves {moves}")
+}
+
 fn main() {
 	let mut arguments = argh::from_env::<Arguments>();
 
@@ -212,27 +362,124 @@ fn main() {
 		arguments.constant_fold = true;
 		arguments.load_store_elide = true;
 		arguments.relax_dependencies = true;
+		arguments.peephole = true;
+		arguments.schedule = true;
+		arguments
+			.allocator
+			.get_or_insert_with(|| "coalescing".to_string());
 	}
 
 	let input = load_input(arguments.input.as_deref());
-	let data = process_hir(&input, &arguments);
-
 	let output = &mut load_output(arguments.output.as_deref());
 
 	let result = match arguments.target.as_str() {
-		"dot" => Dot::new().write(output, data.nodes(), data.roots()),
+		"dot" => {
+			let data = process_hir(&input, &arguments);
+
+			Dot::new().write(output, data.nodes(), data.roots())
+		}
+		"hir" => {
+			let data = process_hir(&input, &arguments);
+			let mut topological = ReverseTopological::new();
+
+			Printer::new().write(output, data.nodes(), data.roots(), &mut topological)
+		}
+		"mir" => {
+			let program = load_mir(&input, &arguments, false);
+
+			mir::text::write(output, &program)
+		}
+		"stats" => {
+			let program = load_mir(&input, &arguments, false);
+
+			write_stats(output, &program)
+		}
 		"c" => {
-			let program = process_mir(&data);
+			let program = load_mir(&input, &arguments, false);
 
 			codegen::c89::write(output, &program)
 		}
+		"clib" => {
+			let program = load_mir(&input, &arguments, false);
+
+			codegen::c89::write_library(output, &program)
+				.and_then(|()| write_header(arguments.header.as_deref()))
+		}
 		"lua" => {
-			let program = process_mir(&data);
+			let program = load_mir(&input, &arguments, true);
 
 			codegen::lua51::write(output, &program)
 		}
+		"luajit" => {
+			let program = load_mir(&input, &arguments, false);
+
+			codegen::luajit::write(output, &program)
+		}
+		"x86-64" => {
+			let program = load_mir(&input, &arguments, false);
+
+			codegen::x86_64::write(output, &program)
+		}
+		"elf" => {
+			let program = load_mir(&input, &arguments, false);
+
+			codegen::elf::write(output, &program)
+				.and_then(|()| set_executable(arguments.output.as_deref()))
+		}
+		"wat" => {
+			let program = load_mir(&input, &arguments, false);
+
+			codegen::wasm::write_text(output, &program)
+		}
+		"wasm" => {
+			let program = load_mir(&input, &arguments, false);
+
+			codegen::wasm::write_binary(output, &program)
+		}
+		"llvm" => {
+			let program = load_mir(&input, &arguments, false);
+
+			codegen::llvm::write(output, &program)
+		}
+		"qbe" => {
+			let program = load_mir(&input, &arguments, false);
+
+			codegen::qbe::write(output, &program)
+		}
+		"rust" => {
+			let program = load_mir(&input, &arguments, false);
+
+			codegen::rust::write(output, &program)
+		}
+		"js" => {
+			let program = load_mir(&input, &arguments, false);
+
+			codegen::javascript::write(output, &program)
+		}
+		"python" => {
+			let program = load_mir(&input, &arguments, false);
+
+			codegen::python::write(output, &program)
+		}
+		"bf" => {
+			let program = load_mir(&input, &arguments, false);
+			match codegen::brainfxck::Regenerator::new().run(&program) {
+				Ok(code) => writeln!(output, "{code}"),
+				Err(error) => Err(Error::new(ErrorKind::InvalidData, error)),
+			}
+		}
+		#[cfg(feature = "jit")]
+		"jit" => {
+			let program = load_mir(&input, &arguments, false);
+
+			run_jit(output, &program)
+		}
 		target => panic!("unsupported target `{target}`"),
 	};
 
-	result.expect("failed to write output");
+	if let Err(error) = result {
+		eprintln!("error: {error}");
+
+		std::process::exit(1);
+	}
 }
@@ -1,4 +0,0 @@
-mod registers;
-
-pub mod data;
-pub mod sequencer;
@@ -0,0 +1,39 @@
+mod common;
+
+use telepathy::mir::{
+	data::{Instruction, Program},
+	registers::{Coalescing, Registers},
+};
+
+use common::{compile, CORPUS};
+
+fn count_moves(program: &Program) -> usize {
+	program
+		.bodies()
+		.iter()
+		.flat_map(|body| body.iter())
+		.filter(|insn| matches!(insn, Instruction::Move { .. }))
+		.count()
+}
+
+#[test]
+fn coalescing_is_never_worse() {
+	for (name, code) in CORPUS {
+		let simple = compile(code, Registers::new());
+		let coalesced = compile(code, Coalescing::new());
+
+		assert!(
+			coalesced.locals() <= simple.locals(),
+			"`{name}` uses {} locals instead of {}",
+			coalesced.locals(),
+			simple.locals()
+		);
+
+		assert!(
+			count_moves(&coalesced) <= count_moves(&simple),
+			"`{name}` emits {} moves instead of {}",
+			count_moves(&coalesced),
+			count_moves(&simple)
+		);
+	}
+}
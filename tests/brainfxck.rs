@@ -0,0 +1,25 @@
+mod common;
+
+use telepathy::{codegen::brainfxck::Regenerator, mir::registers::Registers};
+
+use common::{compile, interpret, CORPUS};
+
+fn regenerate(code: &str) -> String {
+	let program = compile(code, Registers::new());
+
+	Regenerator::new().run(&program).unwrap().to_string()
+}
+
+#[test]
+fn round_trip() {
+	for (name, code) in CORPUS {
+		let expected = interpret(code, b"stressed");
+		let first = regenerate(code);
+
+		assert_eq!(interpret(&first, b"stressed"), expected, "`{name}`");
+
+		let second = regenerate(&first);
+
+		assert_eq!(interpret(&second, b"stressed"), expected, "`{name}`");
+	}
+}
@@ -0,0 +1,84 @@
+use std::{fs, process::Command};
+
+use telepathy::{
+	codegen::c89::{write_header, write_library},
+	mir::text::Reader,
+};
+
+// Counts down from the input byte, then moves off the tape.
+static PROGRAM: &str = "locals 5
+r0 = integer 0
+r1 = ask
+store r0, r1
+r2 = integer 1
+repeat {
+	r3 = load r0
+	tell r3
+	r3 = sub r3, r2
+	store r0, r3
+} while r3
+r4 = integer 18446744073709551516
+store r4, r2
+";
+
+static DRIVER: &str = r#"#include <stdio.h>
+
+#include "bf_run.h"
+
+static int ask(void *ctx) {
+	const char **input = ctx;
+
+	return **input ? (unsigned char) *(*input)++ : -1;
+}
+
+static void tell(void *ctx, int value) {
+	(void) ctx;
+	putchar(value);
+}
+
+int main(void) {
+	const char *input = "\003";
+	uint8_t tape[16] = { 0 };
+
+	printf("%d", bf_run(&input, ask, tell, tape, sizeof tape));
+
+	return 0;
+}
+"#;
+
+#[test]
+fn library_compiles_with_header() {
+	let program = Reader::new().read(PROGRAM).unwrap();
+	let directory = std::env::temp_dir().join(format!("telepathy-c89-{}", std::process::id()));
+	let mut library = Vec::new();
+	let mut header = Vec::new();
+
+	write_library(&mut library, &program).unwrap();
+	write_header(&mut header).unwrap();
+
+	fs::create_dir_all(&directory).unwrap();
+	fs::write(directory.join("bf_run.c"), library).unwrap();
+	fs::write(directory.join("bf_run.h"), header).unwrap();
+	fs::write(directory.join("main.c"), DRIVER).unwrap();
+
+	let cc = std::env::var("CC").unwrap_or_else(|_| "cc".to_string());
+	let compiled = Command::new(cc)
+		.args(["-std=c89", "-pedantic", "-Werror", "-o"])
+		.arg(directory.join("main"))
+		.arg(directory.join("main.c"))
+		.arg(directory.join("bf_run.c"))
+		.output()
+		.unwrap();
+
+	assert!(
+		compiled.status.success(),
+		"{}",
+		String::from_utf8_lossy(&compiled.stderr)
+	);
+
+	let run = Command::new(directory.join("main")).output().unwrap();
+
+	fs::remove_dir_all(&directory).unwrap();
+
+	assert_eq!(run.stdout, b"\x03\x02\x01-1");
+}
@@ -0,0 +1,91 @@
+// Each test crate uses only some of these helpers.
+#![allow(dead_code)]
+
+use regioned::visit::reverse_topological::ReverseTopological;
+use telepathy::{
+	hir::{optimizer::Optimizer, parser::Parser},
+	mir::{data::Program, peephole::Peephole, registers::Allocator, sequencer::Sequencer},
+};
+
+/// Small programs covering loops, nesting, I/O and unbalanced loops.
+pub const CORPUS: [(&str, &str); 8] = [
+	("add", include_str!("../corpus/add.bf")),
+	("cat", include_str!("../corpus/cat.bf")),
+	("copy", include_str!("../corpus/copy.bf")),
+	("hello", include_str!("../corpus/hello.bf")),
+	("nest", include_str!("../corpus/nest.bf")),
+	("red", include_str!("../corpus/red.bf")),
+	("rev", include_str!("../corpus/rev.bf")),
+	("unb", include_str!("../corpus/unb.bf")),
+];
+
+/// Compiles the source with every optimization, as `-O` does.
+pub fn compile<A: Allocator>(code: &str, allocator: A) -> Program {
+	compile_with(code, allocator, true)
+}
+
+/// Compiles the source with every optimization, scheduling nodes only if
+/// asked to.
+pub fn compile_with<A: Allocator>(code: &str, allocator: A, scheduling: bool) -> Program {
+	let mut data = Parser::new().parse(code.char_indices()).unwrap();
+
+	Optimizer::with_all().run(&mut data);
+
+	let mut program = Sequencer::with_allocator(allocator)
+		.with_scheduling(scheduling)
+		.sequence(&data, &mut ReverseTopological::new());
+
+	Peephole::new().run(&mut program);
+
+	program
+}
+
+/// Runs the source on a tape that wraps around, reading zero past the end
+/// of the input.
+pub fn interpret(code: &str, input: &[u8]) -> Vec<u8> {
+	let code = code.as_bytes();
+	let mut jumps = vec![0; code.len()];
+	let mut starts = Vec::new();
+
+	for (index, &byte) in code.iter().enumerate() {
+		match byte {
+			b'[' => starts.push(index),
+			b']' => {
+				let start = starts.pop().unwrap();
+
+				jumps[start] = index;
+				jumps[index] = start;
+			}
+			_ => {}
+		}
+	}
+
+	let mut tape = vec![0_u8; 1 << 16];
+	let mut head = 0;
+	let mut input = input.iter();
+	let mut output = Vec::new();
+	let mut index = 0;
+	let mut steps = 0;
+
+	while index < code.len() {
+		steps += 1;
+
+		assert!(steps < 10_000_000, "program does not halt");
+
+		match code[index] {
+			b'+' => tape[head] = tape[head].wrapping_add(1),
+			b'-' => tape[head] = tape[head].wrapping_sub(1),
+			b'>' => head = (head + 1) % tape.len(),
+			b'<' => head = (head + tape.len() - 1) % tape.len(),
+			b'.' => output.push(tape[head]),
+			b',' => tape[head] = input.next().copied().unwrap_or(0),
+			b'[' if tape[head] == 0 => index = jumps[index],
+			b']' if tape[head] != 0 => index = jumps[index],
+			_ => {}
+		}
+
+		index += 1;
+	}
+
+	output
+}
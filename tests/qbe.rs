@@ -0,0 +1,26 @@
+use telepathy::{codegen::qbe::write, mir::text::Reader};
+
+fn check(program: &str, expected: &str) {
+	let program = Reader::new().read(program).unwrap();
+	let mut output = Vec::new();
+
+	write(&mut output, &program).unwrap();
+
+	assert_eq!(String::from_utf8(output).unwrap(), expected);
+}
+
+#[test]
+fn golden_cat() {
+	check(
+		include_str!("golden/cat.mir"),
+		include_str!("golden/cat.ssa"),
+	);
+}
+
+#[test]
+fn golden_states() {
+	check(
+		include_str!("golden/states.mir"),
+		include_str!("golden/states.ssa"),
+	);
+}
@@ -0,0 +1,61 @@
+use std::{fs, process::Command};
+
+use telepathy::{
+	codegen::rust::{write, write_expression},
+	mir::text::Reader,
+};
+
+// Both forms are pulled in with `include!`, which rejects inner attributes.
+static MAIN: &str = r#"#![deny(warnings)]
+
+mod program {
+	include!("program.rs");
+}
+
+fn main() {
+	let run: fn(&mut dyn std::io::Read, &mut dyn std::io::Write) -> std::io::Result<()> =
+		include!("expression.rs");
+	let mut output = Vec::new();
+
+	run(&mut &[3_u8][..], &mut output).unwrap();
+	program::run(&output[..], std::io::stdout()).unwrap();
+}
+"#;
+
+#[test]
+fn compiles_without_warnings() {
+	let program = Reader::new()
+		.read(include_str!("golden/countdown.mir"))
+		.unwrap();
+	let directory = std::env::temp_dir().join(format!("telepathy-rust-{}", std::process::id()));
+	let mut module = Vec::new();
+	let mut expression = Vec::new();
+
+	write(&mut module, &program).unwrap();
+	write_expression(&mut expression, &program).unwrap();
+
+	fs::create_dir_all(&directory).unwrap();
+	fs::write(directory.join("program.rs"), module).unwrap();
+	fs::write(directory.join("expression.rs"), expression).unwrap();
+	fs::write(directory.join("main.rs"), MAIN).unwrap();
+
+	let rustc = std::env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+	let compiled = Command::new(rustc)
+		.args(["--edition", "2021", "-o"])
+		.arg(directory.join("main"))
+		.arg(directory.join("main.rs"))
+		.output()
+		.unwrap();
+
+	assert!(
+		compiled.status.success(),
+		"{}",
+		String::from_utf8_lossy(&compiled.stderr)
+	);
+
+	let run = Command::new(directory.join("main")).output().unwrap();
+
+	fs::remove_dir_all(&directory).unwrap();
+
+	assert_eq!(run.stdout, [3, 2, 1]);
+}
@@ -0,0 +1,41 @@
+mod common;
+
+use telepathy::{
+	codegen::brainfxck::Regenerator,
+	mir::registers::{Coalescing, Registers},
+};
+
+use common::{compile_with, interpret, CORPUS};
+
+#[test]
+fn scheduling_keeps_output() {
+	for (name, code) in CORPUS {
+		let expected = interpret(code, b"stressed");
+
+		for scheduling in [false, true] {
+			let program = compile_with(code, Registers::new(), scheduling);
+			let regenerated = Regenerator::new().run(&program).unwrap().to_string();
+
+			assert_eq!(
+				interpret(&regenerated, b"stressed"),
+				expected,
+				"`{name}` with scheduling {scheduling}"
+			);
+		}
+	}
+}
+
+#[test]
+fn scheduling_is_never_worse() {
+	for (name, code) in CORPUS {
+		let plain = compile_with(code, Coalescing::new(), false);
+		let scheduled = compile_with(code, Coalescing::new(), true);
+
+		assert!(
+			scheduled.locals() <= plain.locals(),
+			"`{name}` uses {} locals instead of {}",
+			scheduled.locals(),
+			plain.locals()
+		);
+	}
+}